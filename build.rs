@@ -80,19 +80,43 @@ fn main() {
         run_make(&out_dir_32);
     }
 
+    // Build the libraries preloaded into targets: the comparison hook of the
+    // cmphook solver and the forkserver
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let compiler = cc::Build::new()
+        .flag("-fno-builtin")
+        .flag("-fPIC")
+        .get_compiler();
+    for name in &["cmphook", "forkserver"] {
+        let src = format!("{}/{}.c", name, name);
+        println!("cargo:rerun-if-changed={}", src);
+        if !compiler
+            .to_command()
+            .args(["-shared", "-o"])
+            .arg(out_dir.join(format!("libb7{}.so", name)))
+            .arg(&src)
+            .arg("-ldl")
+            .status()
+            .expect("Failed to run cc")
+            .success()
+        {
+            panic!("Failed to build {}!", name);
+        }
+    }
+
     // Generate Rust bindings
     let bindings = bindgen::Builder::default()
         .header("src/bindgen.h")
         .whitelist_type("perf_event_attr")
         .whitelist_type("perf_type_id")
         .whitelist_type("perf_hw_id")
+        .whitelist_type("perf_sw_ids")
+        .whitelist_type("perf_event_read_format")
         .generate()
         .expect("Unable to generate bindings");
 
     // Output rust bindings to a file
-    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
-
     bindings
-        .write_to_file(out_path.join("bindings.rs"))
+        .write_to_file(out_dir.join("bindings.rs"))
         .expect("Couldn't write bindings!");
 }
@@ -0,0 +1,77 @@
+// Preloaded into the target by the cmphook solver. Reports the longest
+// matching prefix seen by strcmp, strncmp and memcmp over the pipe whose
+// write end is in B7_CMPHOOK_FD, as native-endian 64 bit integers.
+#define _GNU_SOURCE
+#include <dlfcn.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+#include <unistd.h>
+
+static int report_fd = -1;
+static uint64_t best;
+
+static int (*real_strcmp)(const char *, const char *);
+static int (*real_strncmp)(const char *, const char *, size_t);
+static int (*real_memcmp)(const void *, const void *, size_t);
+
+__attribute__((constructor)) static void init(void) {
+    const char *fd = getenv("B7_CMPHOOK_FD");
+    if (fd) {
+        report_fd = atoi(fd);
+    }
+    real_strcmp = dlsym(RTLD_NEXT, "strcmp");
+    real_strncmp = dlsym(RTLD_NEXT, "strncmp");
+    real_memcmp = dlsym(RTLD_NEXT, "memcmp");
+}
+
+// Only new maximums are written so the pipe can't fill up
+static void report(uint64_t prefix) {
+    if (report_fd < 0 || prefix <= best) {
+        return;
+    }
+    best = prefix;
+    ssize_t written = write(report_fd, &best, sizeof(best));
+    (void)written;
+}
+
+static size_t str_prefix(const char *a, const char *b, size_t n) {
+    size_t i = 0;
+    while (i < n && a[i] && a[i] == b[i]) {
+        i++;
+    }
+    return i;
+}
+
+static size_t mem_prefix(const unsigned char *a, const unsigned char *b,
+                         size_t n) {
+    size_t i = 0;
+    while (i < n && a[i] == b[i]) {
+        i++;
+    }
+    return i;
+}
+
+int strcmp(const char *a, const char *b) {
+    if (!real_strcmp) {
+        init();
+    }
+    report(str_prefix(a, b, SIZE_MAX));
+    return real_strcmp(a, b);
+}
+
+int strncmp(const char *a, const char *b, size_t n) {
+    if (!real_strncmp) {
+        init();
+    }
+    report(str_prefix(a, b, n));
+    return real_strncmp(a, b, n);
+}
+
+int memcmp(const void *a, const void *b, size_t n) {
+    if (!real_memcmp) {
+        init();
+    }
+    report(mem_prefix(a, b, n));
+    return real_memcmp(a, b, n);
+}
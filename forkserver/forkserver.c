@@ -0,0 +1,272 @@
+// Preloaded into the target by the forkserver. Once the target is loaded and
+// libc is initialized, it forks a child for every request from b7, so only
+// the first run pays for exec and dynamic loading.
+//
+// The fds to talk to b7 are in B7_FORKSERVER_FD as `<ctl>,<status>`. Children
+// are forked at main, or at the address in B7_FORKSERVER_ADDR (hex, relative
+// to the executable base) if it is set.
+//
+// Request on ctl, native endian:
+//   u32 argc, argc times (u32 len, bytes)    arguments after argv[0]
+//   u32 len, bytes                           stdin
+//   u32 count, count times (u64 addr, u32 len, bytes)   memory to write
+// Replies on status:
+//   i32 pid of the child, or -1 if it could not be forked
+//   i32 wait status, u64 stdout length       once the child exited
+// The child waits for a byte on ctl before it runs, so b7 can attach its
+// counters first.
+#define _GNU_SOURCE
+#include <dlfcn.h>
+#include <link.h>
+#include <signal.h>
+#include <stdint.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/stat.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <ucontext.h>
+#include <unistd.h>
+#include <fcntl.h>
+
+struct mem {
+    uint64_t addr;
+    uint32_t len;
+    char *bytes;
+};
+
+struct request {
+    uint32_t argc;
+    char **argv;
+    uint32_t stdin_len;
+    char *stdin_bytes;
+    uint32_t mem_count;
+    struct mem *mem;
+};
+
+typedef int (*main_fn)(int, char **, char **);
+
+static int ctl_fd = -1;
+static int status_fd = -1;
+static uintptr_t base;
+static uintptr_t fork_addr;
+static unsigned char saved_byte;
+static main_fn real_main;
+
+static int read_all(int fd, void *buf, size_t len) {
+    char *pos = buf;
+    while (len) {
+        ssize_t n = read(fd, pos, len);
+        if (n <= 0) {
+            return -1;
+        }
+        pos += n;
+        len -= n;
+    }
+    return 0;
+}
+
+static int write_all(int fd, const void *buf, size_t len) {
+    const char *pos = buf;
+    while (len) {
+        ssize_t n = write(fd, pos, len);
+        if (n <= 0) {
+            return -1;
+        }
+        pos += n;
+        len -= n;
+    }
+    return 0;
+}
+
+static char *read_bytes(uint32_t *len) {
+    if (read_all(ctl_fd, len, sizeof(*len))) {
+        return NULL;
+    }
+    char *bytes = malloc(*len + 1);
+    if (!bytes || read_all(ctl_fd, bytes, *len)) {
+        free(bytes);
+        return NULL;
+    }
+    bytes[*len] = 0;
+    return bytes;
+}
+
+static void free_request(struct request *req) {
+    for (uint32_t i = 0; req->argv && i < req->argc; i++) {
+        free(req->argv[i]);
+    }
+    for (uint32_t i = 0; req->mem && i < req->mem_count; i++) {
+        free(req->mem[i].bytes);
+    }
+    free(req->argv);
+    free(req->stdin_bytes);
+    free(req->mem);
+}
+
+static int read_request(struct request *req) {
+    uint32_t len;
+    memset(req, 0, sizeof(*req));
+    if (read_all(ctl_fd, &req->argc, sizeof(req->argc))) {
+        return -1;
+    }
+    req->argv = calloc(req->argc + 1, sizeof(char *));
+    for (uint32_t i = 0; i < req->argc; i++) {
+        if (!(req->argv[i] = read_bytes(&len))) {
+            return -1;
+        }
+    }
+    if (!(req->stdin_bytes = read_bytes(&req->stdin_len))) {
+        return -1;
+    }
+    if (read_all(ctl_fd, &req->mem_count, sizeof(req->mem_count))) {
+        return -1;
+    }
+    req->mem = calloc(req->mem_count, sizeof(struct mem));
+    for (uint32_t i = 0; i < req->mem_count; i++) {
+        if (read_all(ctl_fd, &req->mem[i].addr, sizeof(req->mem[i].addr)) ||
+            !(req->mem[i].bytes = read_bytes(&req->mem[i].len))) {
+            return -1;
+        }
+    }
+    return 0;
+}
+
+// /proc/self/mem ignores page protections, like ptrace writes do
+static int write_mem(uintptr_t addr, const void *bytes, size_t len) {
+    int fd = open("/proc/self/mem", O_RDWR);
+    if (fd < 0) {
+        return -1;
+    }
+    ssize_t written = pwrite(fd, bytes, len, addr);
+    close(fd);
+    return written == (ssize_t)len ? 0 : -1;
+}
+
+static int memfd(const char *name) {
+    return syscall(SYS_memfd_create, name, 0);
+}
+
+// Serve requests until b7 closes ctl. Only returns in the children, with
+// argv replaced by the requested arguments if `argc` is not NULL.
+static void serve(int *argc, char ***argv) {
+    for (;;) {
+        struct request req;
+        if (read_request(&req)) {
+            _exit(0);
+        }
+
+        int in = memfd("b7-stdin");
+        int out = memfd("b7-stdout");
+        write_all(in, req.stdin_bytes, req.stdin_len);
+        lseek(in, 0, SEEK_SET);
+
+        pid_t pid = fork();
+        if (pid == 0) {
+            char go;
+            dup2(in, 0);
+            dup2(out, 1);
+            close(in);
+            close(out);
+            for (uint32_t i = 0; i < req.mem_count; i++) {
+                if (write_mem(base + req.mem[i].addr, req.mem[i].bytes,
+                              req.mem[i].len)) {
+                    _exit(127);
+                }
+            }
+            if (read_all(ctl_fd, &go, 1)) {
+                _exit(127);
+            }
+            close(ctl_fd);
+            close(status_fd);
+
+            if (argc) {
+                char **new_argv = calloc(req.argc + 2, sizeof(char *));
+                new_argv[0] = (*argv)[0];
+                memcpy(new_argv + 1, req.argv, req.argc * sizeof(char *));
+                *argc = req.argc + 1;
+                *argv = new_argv;
+            }
+            return;
+        }
+
+        int32_t reply = pid;
+        write_all(status_fd, &reply, sizeof(reply));
+        if (pid > 0) {
+            int status = 0;
+            struct stat st;
+            waitpid(pid, &status, 0);
+            uint64_t stdout_len = fstat(out, &st) ? 0 : st.st_size;
+            reply = status;
+            write_all(status_fd, &reply, sizeof(reply));
+            write_all(status_fd, &stdout_len, sizeof(stdout_len));
+        }
+        close(in);
+        close(out);
+        free_request(&req);
+    }
+}
+
+static int fork_main(int argc, char **argv, char **envp) {
+    serve(&argc, &argv);
+    return real_main(argc, argv, envp);
+}
+
+// Reached the fork address, so put the code back and rewind to it. The
+// children return from the handler and continue from there.
+static void on_trap(int sig, siginfo_t *info, void *context) {
+    ucontext_t *uc = context;
+    (void)sig;
+    (void)info;
+#if defined(__x86_64__)
+    uc->uc_mcontext.gregs[REG_RIP] -= 1;
+#elif defined(__i386__)
+    uc->uc_mcontext.gregs[REG_EIP] -= 1;
+#endif
+    write_mem(fork_addr, &saved_byte, 1);
+    signal(SIGTRAP, SIG_DFL);
+    serve(NULL, NULL);
+}
+
+static int find_base(struct dl_phdr_info *info, size_t size, void *data) {
+    (void)size;
+    (void)data;
+    // The executable is always reported first
+    base = info->dlpi_addr;
+    return 1;
+}
+
+int __libc_start_main(main_fn main, int argc, char **argv, void (*init)(void),
+                      void (*fini)(void), void (*rtld_fini)(void),
+                      void *stack_end) {
+    int (*real_start)(main_fn, int, char **, void (*)(void), void (*)(void),
+                      void (*)(void), void *) =
+        dlsym(RTLD_NEXT, "__libc_start_main");
+    const char *fds = getenv("B7_FORKSERVER_FD");
+    const char *addr = getenv("B7_FORKSERVER_ADDR");
+
+    if (!fds) {
+        return real_start(main, argc, argv, init, fini, rtld_fini, stack_end);
+    }
+    ctl_fd = atoi(fds);
+    status_fd = atoi(strchr(fds, ',') + 1);
+    dl_iterate_phdr(find_base, NULL);
+
+    if (addr) {
+        unsigned char int3 = 0xcc;
+        struct sigaction sa;
+        memset(&sa, 0, sizeof(sa));
+        sa.sa_sigaction = on_trap;
+        sa.sa_flags = SA_SIGINFO;
+        sigaction(SIGTRAP, &sa, NULL);
+
+        fork_addr = base + strtoull(addr, NULL, 16);
+        memcpy(&saved_byte, (void *)fork_addr, 1);
+        write_mem(fork_addr, &int3, 1);
+        return real_start(main, argc, argv, init, fini, rtld_fini, stack_end);
+    }
+
+    real_main = main;
+    return real_start(fork_main, argc, argv, init, fini, rtld_fini, stack_end);
+}
@@ -1,5 +1,6 @@
 use crate::generators::GenItem;
 use crate::generators::Input;
+use crate::measurement::Measurement;
 use log::LevelFilter;
 use std::fs::File;
 use std::io;
@@ -53,7 +54,15 @@ enum Format {
 /// Trait that all Uis will implement to ensure genericness
 pub trait Ui {
     // handle a new ui check
-    fn update(&mut self, results: Box<Vec<(i64, (GenItem, Input))>>, min: u64) -> bool;
+    // `confidence` is how sure the outlier detector is of the round's pick
+    // `measurements` holds what was measured for each of the results
+    fn update(
+        &mut self,
+        results: Box<Vec<(i64, (GenItem, Input))>>,
+        min: u64,
+        confidence: Option<f64>,
+        measurements: &[Measurement],
+    ) -> bool;
     // allow gui to pause if user doesn't want to continue
     fn wait(&mut self) -> bool;
     // separate wait to signify all results are calculated
@@ -75,7 +84,9 @@ pub struct Tui {
         >,
     >,
     size: tui::layout::Rect,
-    cache: Vec<(Vec<(u64, u64)>, u64)>,
+    cache: Vec<(Vec<(u64, u64, Measurement)>, u64, Option<f64>)>,
+    // metric to chart instead of the selected value
+    metric: Option<String>,
     numrun: u64,
     currun: u64,
     gap: u16,
@@ -118,6 +129,7 @@ impl Tui {
             terminal,
             size,
             cache,
+            metric: None,
             numrun: 0,
             currun: 0,
             gap: 0,
@@ -132,6 +144,23 @@ impl Tui {
             options,
         }
     }
+    /// chart the next metric of the current run, going back to the selected
+    /// value after the last one
+    pub fn next_metric(&mut self) {
+        if self.currun == 0 {
+            return;
+        }
+        let names: Vec<String> = match self.cache[(self.currun - 1) as usize].0.first() {
+            Some(s) => s.2.iter().map(|(name, _)| name.to_string()).collect(),
+            None => return,
+        };
+
+        let next = match &self.metric {
+            Some(metric) => names.iter().position(|name| name == metric).map(|i| i + 1),
+            None => Some(0),
+        };
+        self.metric = next.and_then(|i| names.get(i).cloned());
+    }
     pub fn set_path(&mut self, path: String) {
         self.path = Some(path.to_string());
     }
@@ -166,8 +195,27 @@ impl Tui {
         if !self.cache.is_empty() {
             let history = &self.history;
             let graph = &self.cache[(self.currun - 1) as usize];
-            let graph3: Vec<(String, u64)> = graph
+            let metric = &self.metric;
+            let values: Vec<(u64, u64)> = graph
                 .0
+                .iter()
+                .map(|s| match metric {
+                    Some(name) => (s.0, s.2.get(name).unwrap_or(0) as u64),
+                    None => (s.0, s.1),
+                })
+                .collect();
+            let min = match metric {
+                Some(_) => values.iter().map(|s| s.1).min().unwrap_or(0),
+                None => graph.1,
+            };
+            let mut title = match metric {
+                Some(name) => format!("B7 - {}", name),
+                None => "B7".to_string(),
+            };
+            if let Some(confidence) = graph.2 {
+                title += &format!(" - confidence {:.1}%", confidence * 100.0);
+            }
+            let graph3: Vec<(String, u64)> = values
                 .iter()
                 .map(|s| match self.format {
                     Format::Decimal => (format!("{}", s.0), s.1 as u64),
@@ -222,13 +270,13 @@ impl Tui {
                     match app.tabs.index {
                         0 => {
                             BarChart::default()
-                                .block(Block::default().title("B7").borders(Borders::ALL))
+                                .block(Block::default().title(&title).borders(Borders::ALL))
                                 .data({
                                     // convert String to &str and chop off uneccesary instructions
                                     graph2 = graph3
                                         .iter()
                                         .map(|s| {
-                                            let adjusted = s.1 - graph.1;
+                                            let adjusted = s.1 - min;
                                             (&*s.0, adjusted)
                                         })
                                         .collect::<Vec<(&str, u64)>>();
@@ -287,7 +335,9 @@ impl Tui {
                                 Text::styled("d", Style::default().modifier(Modifier::Bold)),
                                 Text::raw(" to convert to decimal, "),
                                 Text::styled("s", Style::default().modifier(Modifier::Bold)),
-                                Text::raw(" to convert to string\n"),
+                                Text::raw(" to convert to string, "),
+                                Text::styled("m", Style::default().modifier(Modifier::Bold)),
+                                Text::raw(" to switch charted metric\n"),
                                 Text::styled("-", Style::default().modifier(Modifier::Bold)),
                                 Text::raw(" and "),
                                 Text::styled("=", Style::default().modifier(Modifier::Bold)),
@@ -343,22 +393,33 @@ impl Ui for Tui {
         return Duration::new(self.timeout, 0);
     }
     /// draw bargraph for new input
-    fn update(&mut self, mut results: Box<Vec<(i64, (GenItem, Input))>>, min: u64) -> bool {
+    fn update(
+        &mut self,
+        results: Box<Vec<(i64, (GenItem, Input))>>,
+        min: u64,
+        confidence: Option<f64>,
+        measurements: &[Measurement],
+    ) -> bool {
         // convertcachefor barchart
-        results.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        let mut results: Vec<_> = results.iter().zip(measurements).collect();
+        results.sort_by(|a, b| (a.0).0.partial_cmp(&(b.0).0).unwrap());
 
         // TODO implement multiple formats
-        let graph: Vec<(String, u64)>;
+        let graph: Vec<(String, u64, Measurement)>;
         graph = results
             .iter()
-            .map(|s| (format!("{}", (s.1).0), s.0 as u64))
+            .map(|s| {
+                let result = s.0;
+                (format!("{}", (result.1).0), result.0 as u64, s.1.clone())
+            })
             .collect();
         self.cache.push((
             graph
-                .iter()
-                .map(|s| ((s.0.parse::<u64>().unwrap()), s.1))
-                .collect::<Vec<(u64, u64)>>(),
+                .into_iter()
+                .map(|s| ((s.0.parse::<u64>().unwrap()), s.1, s.2))
+                .collect::<Vec<(u64, u64, Measurement)>>(),
             min,
+            confidence,
         ));
         if self.currun == self.numrun {
             self.currun += 1;
@@ -378,6 +439,7 @@ impl Ui for Tui {
                     Ok(Key::Char('h')) => self.format = Format::Hex,
                     Ok(Key::Char('d')) => self.format = Format::Decimal,
                     Ok(Key::Char('s')) => self.format = Format::String,
+                    Ok(Key::Char('m')) => self.next_metric(),
                     Ok(Key::Char('c')) => {
                         self.cont ^= true;
                         if self.cont {
@@ -512,6 +574,7 @@ impl Ui for Tui {
                 Ok(Key::Char('h')) => self.format = Format::Hex,
                 Ok(Key::Char('d')) => self.format = Format::Decimal,
                 Ok(Key::Char('s')) => self.format = Format::String,
+                Ok(Key::Char('m')) => self.next_metric(),
                 Ok(Key::Right) => {
                     if self.currun < self.numrun {
                         self.currun += 1;
@@ -585,7 +648,16 @@ impl Env {
 
 // default do nothing just let the prints handle it
 impl Ui for Env {
-    fn update(&mut self, mut _results: Box<Vec<(i64, (GenItem, Input))>>, _min: u64) -> bool {
+    fn update(
+        &mut self,
+        mut _results: Box<Vec<(i64, (GenItem, Input))>>,
+        _min: u64,
+        confidence: Option<f64>,
+        _measurements: &[Measurement],
+    ) -> bool {
+        if let Some(confidence) = confidence {
+            info!("Outlier confidence: {:.1}%", confidence * 100.0);
+        }
         true
     }
     fn set_timeout(&mut self, _timeout: Duration) {
@@ -0,0 +1,342 @@
+use scoped_pool::Pool;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::Path;
+
+use crate::b7tui;
+use crate::brute::{run_round, InstCounter, Sampling};
+use crate::errors::*;
+use crate::generators::{Generate, Input};
+use crate::measurement::Selector;
+use crate::statistics::{self, NoiseFloor, OutlierDetector};
+
+/// Why the beam search dropped a branch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PruneReason {
+    /// No input of the branch's round stood out, or none above the noise
+    /// floor, while one did in another branch of the round. An earlier
+    /// choice of the branch was probably wrong.
+    NoSignal,
+    /// Every input of the branch's round failed to run
+    NoResults,
+}
+
+impl std::fmt::Display for PruneReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PruneReason::NoSignal => write!(f, "no input stood out"),
+            PruneReason::NoResults => write!(f, "every input failed to run"),
+        }
+    }
+}
+
+/// A branch the beam search dropped
+#[derive(Debug, Clone)]
+pub struct Pruned {
+    /// Input the branch solved so far, as shown by its generator
+    pub branch: String,
+    /// Round the branch was dropped in, starting at 1
+    pub round: usize,
+    pub reason: PruneReason,
+}
+
+/// Result of a beam search
+#[derive(Debug, Clone)]
+pub struct BeamReport {
+    /// Input of the branch that finished first
+    pub input: Input,
+    /// Branches that were dropped on the way
+    pub pruned: Vec<Pruned>,
+    /// Runs of contenders the adaptive sampling added
+    pub extra_runs: u32,
+}
+
+/// A partial solution the beam search follows
+#[derive(Debug, Clone)]
+struct Branch<G> {
+    gen: G,
+    /// Input chosen in the branch's last round
+    input: Input,
+    /// Sum of how far the chosen inputs stood out in each round, in median
+    /// absolute deviations
+    score: f64,
+}
+
+/// Like `brute`, but instead of committing to the outlier of every round,
+/// keeps the `width` best branches, extends each of them with its `width`
+/// best inputs and keeps the best of those. A branch whose round shows no
+/// outlier is pruned if another branch of the round shows one, otherwise
+/// it is only extended with its best input. Once every branch of a round is
+/// pruned, the search backtracks to the branches it kept in reserve in the
+/// rounds before.
+///
+/// Branches are copies of `gen`, which is set to the branch that finished.
+pub fn beam<P: AsRef<Path>, G: Generate + Display + Clone>(
+    path: P,
+    repeat: u32,
+    gen: &mut G,
+    counter: &dyn InstCounter,
+    selector: &Selector,
+    detector: &dyn OutlierDetector,
+    sampling: Option<Sampling>,
+    floor: Option<NoiseFloor>,
+    solved: Input,
+    terminal: &mut dyn b7tui::Ui,
+    vars: HashMap<String, String>,
+    drop_ptrace: bool,
+    width: usize,
+) -> Result<BeamReport, SolverError> {
+    let path = path.as_ref();
+    let width = width.max(1);
+
+    let pool = Pool::new(num_cpus::get());
+
+    let mut pruned = Vec::new();
+    let mut extra_runs = 0;
+    detector.reset();
+    // Branches of each round, best first. The first `width` of the last
+    // round are extended next, the others are kept for backtracking.
+    let mut rounds = vec![vec![Branch {
+        gen: gen.clone(),
+        input: solved.clone(),
+        score: 0.0,
+    }]];
+
+    loop {
+        // Backtrack once every branch of the last round was tried
+        while rounds.last().map_or(false, Vec::is_empty) {
+            rounds.pop();
+            if !rounds.is_empty() {
+                warn!("Backtracking to round {}", rounds.len());
+            }
+        }
+        let depth = rounds.len();
+        let round = match rounds.last_mut() {
+            Some(round) => round,
+            None => {
+                return Err(SolverError::new(
+                    Runner::Unknown,
+                    "Every branch of the beam search was pruned",
+                ))
+            }
+        };
+        let beam: Vec<_> = round.drain(..width.min(round.len())).collect();
+
+        // Run the round of every branch first, since whether a branch without
+        // signal is pruned depends on the others
+        let mut outcomes = Vec::with_capacity(beam.len());
+        for mut branch in beam {
+            let outcome = loop {
+                let data = branch.gen.by_ref().collect();
+                let outcome = run_round(
+                    path,
+                    repeat,
+                    data,
+                    counter,
+                    selector,
+                    detector,
+                    sampling,
+                    floor,
+                    &solved,
+                    terminal,
+                    &vars,
+                    drop_ptrace,
+                    &pool,
+                )?;
+                extra_runs += outcome.extra_runs;
+                // Let the generator try other inputs if none stood out
+                if outcome.results.is_empty() || outcome.signal || !branch.gen.no_signal() {
+                    break outcome;
+                }
+            };
+            outcomes.push((branch, outcome));
+        }
+        let any_signal = outcomes
+            .iter()
+            .any(|(_, outcome)| !outcome.results.is_empty() && outcome.signal);
+
+        // Learn from the round of the best branch that is followed
+        let followed = outcomes
+            .iter()
+            .filter(|(_, outcome)| outcome.signal || !any_signal)
+            .find_map(|(_, outcome)| outcome.pick.map(|pick| (&outcome.results, pick)));
+        if let Some((results, pick)) = followed {
+            let counts: Vec<i64> = results.iter().map(|result| result.0).collect();
+            detector.commit(&counts, pick);
+        }
+
+        let mut children = Vec::new();
+        let mut finished = Vec::new();
+        for (branch, outcome) in outcomes {
+            let results = outcome.results;
+
+            let reason = if results.is_empty() {
+                Some(PruneReason::NoResults)
+            } else if !outcome.signal && any_signal {
+                Some(PruneReason::NoSignal)
+            } else {
+                None
+            };
+            if let Some(reason) = reason {
+                warn!(
+                    "Pruned branch {} in round {}: {}",
+                    branch.gen, depth, reason
+                );
+                pruned.push(Pruned {
+                    branch: branch.gen.to_string(),
+                    round: depth,
+                    reason,
+                });
+                continue;
+            }
+
+            // Without a signal anywhere, follow only the best guess
+            let take = if outcome.signal { width } else { 1 };
+            for (idx, distance) in statistics::rank_outliers(&results, detector.direction())
+                .into_iter()
+                .take(take)
+            {
+                let (item, input) = results[idx].1.clone();
+                let mut child = Branch {
+                    gen: branch.gen.clone(),
+                    input,
+                    score: branch.score + distance,
+                };
+                if child.gen.update(item) {
+                    children.push(child);
+                } else {
+                    finished.push(child);
+                }
+            }
+        }
+
+        let by_score = |a: &Branch<G>, b: &Branch<G>| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        };
+        finished.sort_by(by_score);
+        if let Some(best) = finished.into_iter().next() {
+            *gen = best.gen;
+            return Ok(BeamReport {
+                input: best.input,
+                pruned,
+                extra_runs,
+            });
+        }
+
+        // Keep the next beam and as many branches in reserve to backtrack to
+        children.sort_by(by_score);
+        children.truncate(width * 2);
+        rounds.push(children);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{beam, BeamReport, PruneReason};
+    use crate::b7tui::Env;
+    use crate::brute::{InstCountData, InstCounter};
+    use crate::charset::Charset;
+    use crate::errors::*;
+    use crate::generators::{Input, StdinCharGenerator};
+    use crate::measurement::{Measurement, Selector};
+    use crate::statistics::MedianMad;
+    use std::collections::HashMap;
+
+    /// Counts what a function of stdin returns, failing where it returns
+    /// `None`
+    struct Oracle(fn(&[u8]) -> Option<i64>);
+
+    impl InstCounter for Oracle {
+        fn get_inst_count(&self, data: &InstCountData) -> Result<Measurement, SolverError> {
+            let stdin = data.inp.stdin.clone().unwrap_or_default();
+            match (self.0)(&stdin) {
+                Some(count) => Ok(Measurement::new(count)),
+                None => Err(SolverError::new(Runner::RunnerError, "oracle failed")),
+            }
+        }
+    }
+
+    /// Search `len` bytes of stdin from the charset `abc`
+    fn search(len: u32, width: usize, oracle: Oracle) -> SolverResult<BeamReport> {
+        let input = Input {
+            stdinlen: Some(len),
+            ..Input::default()
+        };
+        let mut gen = StdinCharGenerator::new(input, Charset::parse_from_arg("abc").unwrap());
+        beam(
+            "target",
+            1,
+            &mut gen,
+            &oracle,
+            &Selector::default(),
+            &MedianMad,
+            None,
+            None,
+            Input::new(),
+            &mut Env::default(),
+            HashMap::new(),
+            false,
+            width,
+        )
+    }
+
+    #[test]
+    fn prunes_without_signal_next_to_signal() {
+        // Only the branch starting with `a` has a second byte that stands out
+        let report = search(
+            2,
+            2,
+            Oracle(|stdin| Some(100 * (stdin[0] == b'a') as i64 + 50 * (stdin == b"aa") as i64)),
+        )
+        .unwrap();
+        assert_eq!(report.input.stdin, Some(b"aa".to_vec()));
+        assert_eq!(report.pruned.len(), 1);
+        assert_eq!(report.pruned[0].branch, "b");
+        assert_eq!(report.pruned[0].round, 2);
+        assert_eq!(report.pruned[0].reason, PruneReason::NoSignal);
+    }
+
+    #[test]
+    fn keeps_best_guess_without_any_signal() {
+        let report = search(2, 2, Oracle(|_| Some(10))).unwrap();
+        assert!(report.pruned.is_empty());
+        assert_eq!(report.input.stdin.map(|stdin| stdin.len()), Some(2));
+    }
+
+    #[test]
+    fn backtracks_to_reserve() {
+        // Every third byte after `a` fails, so the beam of `aa` and `ab`
+        // dies and the search goes back to `ba` and `bb`
+        let report = search(
+            3,
+            2,
+            Oracle(|stdin| {
+                if stdin[0] == b'a' && stdin[2] != b'A' {
+                    return None;
+                }
+                let mut count = 0;
+                for (byte, weight) in stdin.iter().zip(&[100, 50, 25]) {
+                    if *byte == b'a' {
+                        count += weight;
+                    }
+                }
+                Some(count)
+            }),
+        )
+        .unwrap();
+        assert_eq!(report.input.stdin, Some(b"baa".to_vec()));
+        let pruned: Vec<_> = report.pruned.iter().map(|p| p.branch.as_str()).collect();
+        assert_eq!(pruned, vec!["aa", "ab"]);
+        assert!(report
+            .pruned
+            .iter()
+            .all(|p| p.round == 3 && p.reason == PruneReason::NoResults));
+    }
+
+    #[test]
+    fn fails_once_everything_is_pruned() {
+        assert!(search(2, 2, Oracle(|_| None)).is_err());
+    }
+}
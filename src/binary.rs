@@ -2,7 +2,9 @@ use goblin::elf::header::Header as ElfHeader;
 use goblin::elf::header::ET_DYN;
 use goblin::elf::header::ET_EXEC;
 use goblin::elf::Elf;
+use iced_x86::{Decoder, DecoderOptions, FlowControl};
 
+use std::collections::HashSet;
 use std::path::Path;
 use std::path::PathBuf;
 
@@ -42,4 +44,107 @@ impl Binary {
             )),
         }
     }
+
+    /// Find the address of the symbol called `name` in the symbol table or the
+    /// dynamic symbol table. For PIE binaries, the address is relative to the
+    /// executable base.
+    pub fn symbol_addr(&self, name: &str) -> SolverResult<usize> {
+        debug!("Executing symbol_addr:");
+        let bytes = std::fs::read(&self.path)?;
+        let elf = Elf::parse(&bytes)?;
+
+        let syms = elf
+            .syms
+            .iter()
+            .map(|sym| (sym, &elf.strtab))
+            .chain(elf.dynsyms.iter().map(|sym| (sym, &elf.dynstrtab)));
+        for (sym, strtab) in syms {
+            if sym.st_value == 0 {
+                continue;
+            }
+            if let Some(Ok(sym_name)) = strtab.get(sym.st_name) {
+                if sym_name == name {
+                    return Ok(sym.st_value as usize);
+                }
+            }
+        }
+
+        Err(SolverError::new(
+            ArgError,
+            &format!("No symbol named {}", name),
+        ))
+    }
+
+    /// Find the start addresses of the basic blocks in `.text`, sorted. For
+    /// PIE binaries, the addresses are relative to the executable base.
+    ///
+    /// `.text` is disassembled linearly. Blocks start at function symbols,
+    /// branch and call targets, and after every instruction that changes
+    /// control flow. Only addresses that are instruction boundaries are kept,
+    /// so a breakpoint can be placed at each of them.
+    pub fn basic_blocks(&self) -> SolverResult<Vec<usize>> {
+        debug!("Executing basic_blocks:");
+        let bytes = std::fs::read(&self.path)?;
+        let elf = Elf::parse(&bytes)?;
+
+        let text = elf
+            .section_headers
+            .iter()
+            .find(|sh| match elf.shdr_strtab.get(sh.sh_name) {
+                Some(Ok(name)) => name == ".text",
+                _ => false,
+            })
+            .ok_or_else(|| SolverError::new(ArgError, "Binary has no .text section"))?;
+
+        let offset = text.sh_offset as usize;
+        let code = bytes
+            .get(offset..offset + text.sh_size as usize)
+            .ok_or_else(|| SolverError::new(ArgError, "Invalid .text section"))?;
+
+        let mut leaders = HashSet::new();
+        leaders.insert(text.sh_addr);
+        for sym in elf.syms.iter() {
+            if sym.is_function() {
+                leaders.insert(sym.st_value);
+            }
+        }
+
+        let bitness = if elf.is_64 { 64 } else { 32 };
+        let mut decoder = Decoder::new(bitness, code, DecoderOptions::NONE);
+        decoder.set_ip(text.sh_addr);
+
+        let mut boundaries = HashSet::new();
+        let mut after_branch = false;
+        while decoder.can_decode() {
+            let insn = decoder.decode();
+            if insn.is_invalid() {
+                after_branch = true;
+                continue;
+            }
+
+            boundaries.insert(insn.ip());
+            if after_branch {
+                leaders.insert(insn.ip());
+            }
+
+            after_branch = match insn.flow_control() {
+                FlowControl::Next => false,
+                FlowControl::UnconditionalBranch
+                | FlowControl::ConditionalBranch
+                | FlowControl::Call => {
+                    leaders.insert(insn.near_branch_target());
+                    true
+                }
+                _ => true,
+            };
+        }
+
+        let mut blocks = leaders
+            .intersection(&boundaries)
+            .map(|&addr| addr as usize)
+            .collect::<Vec<usize>>();
+        blocks.sort();
+
+        Ok(blocks)
+    }
 }
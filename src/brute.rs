@@ -11,8 +11,9 @@ use std::time::Duration;
 
 use crate::b7tui;
 use crate::errors::*;
-use crate::generators::{GenItem, Generate, Input};
-use crate::statistics;
+use crate::generators::{parse_arg_opts, GenItem, Generate, Input};
+use crate::measurement::{Measurement, Selector};
+use crate::statistics::{self, NoiseFloor, OutlierDetector, Pick};
 
 #[derive(Clone, Debug)]
 /// holds information that is universal to InstCounters
@@ -25,9 +26,12 @@ pub struct InstCountData {
 }
 
 pub trait InstCounter: Send + Sync + 'static {
-    /// function that runs a program and returns a number representing progress in a binary
+    /// function that runs a program and returns a measurement of its progress in a binary
     /// runs passed on info in data
-    fn get_inst_count(&self, data: &InstCountData) -> Result<i64, SolverError>;
+    fn get_inst_count(&self, data: &InstCountData) -> Result<Measurement, SolverError>;
+
+    /// adjust the measurements of every input in a round before the outlier is picked
+    fn finish_round(&self, _measurements: &mut [Measurement]) {}
 }
 
 // can take out Debug trait later
@@ -40,6 +44,12 @@ pub trait InstCounter: Send + Sync + 'static {
 /// * `repeat` - an int to tell how many runs to average for each input
 /// * `gen` - a generators::generator that has Display trait to use to generate additional input
 /// * `counter` - the inst_counter function to run the binary under
+/// * `selector` - chooses the value of each measurement to find the outlier in
+/// * `detector` - picks the outlier of each round and says how sure it is
+/// * `sampling` - re-runs the top contenders of rounds `detector` is not sure
+///   about, instead of running every input `repeat` times
+/// * `floor` - the noise floor from `calibrate`, which the outlier of a round
+///   has to exceed to count as a signal
 /// * `Solved` - other constraints to pass to the binary
 /// * `terminal` - a b7tui::Ui to present data to, so it can display it
 /// * `timeout` - a duration in seconds to timeout program after
@@ -55,6 +65,8 @@ pub trait InstCounter: Send + Sync + 'static {
 /// # use crate::b7::b7tui;
 /// # use b7::brute::brute;
 /// # use b7::brute::InstCounter;
+/// # use b7::measurement::Selector;
+/// # use b7::statistics::MedianMad;
 /// use std::collections::HashMap;
 /// use std::time::Duration;
 /// use std::io;
@@ -66,7 +78,11 @@ pub trait InstCounter: Send + Sync + 'static {
 ///        "./tests/wyvern",
 ///        1,
 ///        &mut task,
-///        &perf::PerfSolver,
+///        &perf::PerfSolver::default(),
+///        &Selector::default(),
+///        &MedianMad,
+///        None,
+///        None,
 ///        Input::new(),
 ///        &mut b7tui::Env::new(),
 ///        HashMap::new(),
@@ -84,6 +100,10 @@ pub fn brute<P: AsRef<Path>, G: Generate + Display>(
     repeat: u32,
     gen: &mut G,
     counter: &dyn InstCounter,
+    selector: &Selector,
+    detector: &dyn OutlierDetector,
+    sampling: Option<Sampling>,
+    floor: Option<NoiseFloor>,
     solved: Input,
     terminal: &mut dyn b7tui::Ui,
     vars: HashMap<String, String>,
@@ -95,87 +115,374 @@ pub fn brute<P: AsRef<Path>, G: Generate + Display>(
 
     let pool = Pool::new(n_workers);
 
+    let mut extra_runs = 0;
+    detector.reset();
     // Loop until generator says we are done
     loop {
-        // Number of threads to spawn
+        // run each case of the generators
+        let data = gen.by_ref().collect();
+        let round = run_round(
+            path,
+            repeat,
+            data,
+            counter,
+            selector,
+            detector,
+            sampling,
+            floor,
+            &solved,
+            terminal,
+            &vars,
+            drop_ptrace,
+            &pool,
+        )?;
+        extra_runs += round.extra_runs;
+        let results = round.results;
+
+        // inform generator of the result
+        let pick = match round.pick {
+            Some(pick) => pick,
+            None => {
+                warn!("Results empty {:?}", results);
+                return Err(SolverError::new(Runner::Unknown, "No valid results found"));
+            }
+        };
+        // Let the generator try other inputs if none stood out
+        if !round.signal {
+            if gen.no_signal() {
+                continue;
+            }
+            warn!("No signal at this position: {}", gen);
+        }
+        let counts: Vec<i64> = results.iter().map(|result| result.0).collect();
+        detector.commit(&counts, pick);
+        let good_idx = &results[pick.idx];
+        debug!(
+            "Picked {:?} with confidence {:.3}",
+            (good_idx.1).0,
+            pick.confidence
+        );
+        if !gen.update((good_idx.1).0) {
+            if extra_runs > 0 {
+                info!("{} extra runs to reach confidence", extra_runs);
+            }
+            break Ok((good_idx.1).1.clone());
+        }
+    }
+}
 
-        let mut num_jobs: i64 = 0;
+/// How `brute` re-runs the inputs of a round the outlier detector is not
+/// sure about. Only the top contenders run again, once more each time, and
+/// their value is the mean of their runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sampling {
+    /// Confidence the detector has to reach before the outlier is picked
+    pub confidence: f64,
+    /// Most times a round is re-run
+    pub max_reruns: u32,
+    /// How many of the inputs furthest from the others are re-run
+    pub contenders: usize,
+}
 
-        let (tx, rx) = channel();
+impl Default for Sampling {
+    fn default() -> Self {
+        Sampling {
+            confidence: 0.95,
+            max_reruns: 10,
+            contenders: 3,
+        }
+    }
+}
 
-        let mut data = Vec::new();
+impl Sampling {
+    /// Parse sampling from an argument of the format:
+    ///
+    /// ``` text
+    /// confidence=0.95,max=10,top=3
+    /// ```
+    ///
+    /// Options left out keep their default.
+    pub fn parse_from_arg(arg: &str) -> SolverResult<Self> {
+        debug!("Executing parse_from_arg:");
+        let opts = parse_arg_opts(arg, "Invalid sampling usage")?;
+        let invalid = |opt: &str| {
+            SolverError::new(
+                Runner::ArgError,
+                &format!("Invalid sampling option {}", opt),
+            )
+        };
 
-        // run each case of the generators
-        for inp_pair in gen.by_ref() {
-            data.push((inp_pair.0, solved.clone().combine(inp_pair.1)));
+        let mut sampling = Sampling::default();
+        for (key, value) in opts {
+            match key {
+                "confidence" => sampling.confidence = value.parse().map_err(|_| invalid(key))?,
+                "max" => sampling.max_reruns = value.parse().map_err(|_| invalid(key))?,
+                "top" => sampling.contenders = value.parse().map_err(|_| invalid(key))?,
+                _ => return Err(invalid(key)),
+            }
         }
-        let mut results: Box<Vec<(i64, (GenItem, Input))>> =
-            Box::new(Vec::with_capacity(data.len()));
-        let counter = Arc::new(counter);
-
-        pool.scoped(|scope| {
-            for inp_pair in data {
-                num_jobs += 1;
-                let tx = tx.clone();
-                let test = path.to_path_buf();
-                // give it to a thread to handle
-                let vars = vars.clone();
-                let counter = counter.clone();
-
-                let timeout = terminal.get_timeout();
-
-                scope.execute(move || {
-                    // print out inp variable at the trace level
-                    let inp = (inp_pair.1).clone();
-                    let data = InstCountData {
-                        path: test,
-                        inp,
-                        vars,
-                        timeout,
-                        drop_ptrace,
-                    };
-                    let mut inst_count = counter.get_inst_count(&data);
-                    trace!("inst_count: {:?}", inst_count);
-                    for _ in 1..repeat {
-                        inst_count = counter.get_inst_count(&data);
-                        trace!("inst_count: {:?}", inst_count);
-                    }
-                    let _ = tx.send((inst_count, (inp_pair)));
-                });
+        if sampling.confidence < 0.0 || sampling.confidence > 1.0 {
+            return Err(invalid("confidence"));
+        }
+        if sampling.contenders == 0 {
+            return Err(invalid("top"));
+        }
+        Ok(sampling)
+    }
+}
+
+/// Outcome of `run_round`
+pub(crate) struct Round {
+    /// Values outliers are searched in, with the input they were measured for
+    pub results: Vec<(i64, (GenItem, Input))>,
+    /// Outlier the detector picked, `None` without results
+    pub pick: Option<Pick>,
+    /// Runs of contenders on top of the first run of each input
+    pub extra_runs: u32,
+    /// Whether the outlier stands out from the other results, and from the
+    /// noise floor if there is one
+    pub signal: bool,
+}
+
+/// Run every input of a round with `counter`, each combined with `solved`,
+/// and reduce the measurements to the values outliers are searched in. The
+/// round is shown on `terminal` along with the outlier `detector` picks.
+///
+/// With `sampling`, each input runs once instead of `repeat` times, and the
+/// top contenders are re-run while the detector is not confident enough.
+/// The outlier is a signal if it stands out in the direction of the
+/// detector, and with `floor` only above the noise floor.
+pub(crate) fn run_round(
+    path: &Path,
+    repeat: u32,
+    data: Vec<(GenItem, Input)>,
+    counter: &dyn InstCounter,
+    selector: &Selector,
+    detector: &dyn OutlierDetector,
+    sampling: Option<Sampling>,
+    floor: Option<NoiseFloor>,
+    solved: &Input,
+    terminal: &mut dyn b7tui::Ui,
+    vars: &HashMap<String, String>,
+    drop_ptrace: bool,
+    pool: &Pool,
+) -> Result<Round, SolverError> {
+    let data: Vec<_> = data
+        .into_iter()
+        .map(|inp_pair| (inp_pair.0, solved.clone().combine(inp_pair.1)))
+        .collect();
+    let timeout = terminal.get_timeout();
+    let repeat = if sampling.is_some() { 1 } else { repeat };
+
+    let (inputs, mut samples): (Vec<_>, Vec<_>) = measure(
+        path,
+        repeat,
+        data,
+        counter,
+        vars,
+        timeout,
+        drop_ptrace,
+        pool,
+    )
+    .into_iter()
+    .map(|(inp_pair, measurement)| (inp_pair, vec![measurement]))
+    .unzip();
+
+    let mut reruns = 0;
+    let mut extra_runs = 0;
+    let (results, measurements, pick) = loop {
+        let (results, measurements) = reduce(&inputs, &samples, counter, selector)?;
+        let counts: Vec<i64> = results.iter().map(|result| result.0).collect();
+        let pick = detector.pick(&counts);
+
+        let sampling = match (sampling, pick) {
+            (Some(sampling), Some(pick))
+                if pick.confidence < sampling.confidence && reruns < sampling.max_reruns =>
+            {
+                sampling
             }
-        });
-        // Track the minimum for stats later
-        let mut min: u64 = std::i64::MAX as u64;
-        // Get results from the threads
-
-        for _ in 0..num_jobs {
-            let tmp = rx.recv().unwrap();
-            match tmp.0 {
-                Ok(x) => {
-                    if (x as u64) < min {
-                        min = x as u64;
-                    }
-                    results.push((x, (tmp.1)));
-                }
-                Err(x) => {
-                    warn!("{:?} \n returned: {:?}", (tmp.1).0, x);
-                    continue;
+            _ => break (results, measurements, pick),
+        };
+        reruns += 1;
+
+        // Run the contenders once more
+        let contenders: Vec<_> = statistics::rank_outliers(&results, detector.direction())
+            .into_iter()
+            .take(sampling.contenders)
+            .map(|(idx, _)| (idx, (inputs[idx].1).clone()))
+            .collect();
+        extra_runs += contenders.len() as u32;
+        let measured = measure(
+            path,
+            1,
+            contenders,
+            counter,
+            vars,
+            timeout,
+            drop_ptrace,
+            pool,
+        );
+        for ((idx, _), measurement) in measured {
+            samples[idx].push(measurement);
+        }
+    };
+    if extra_runs > 0 {
+        debug!("{} extra runs in {} reruns", extra_runs, reruns);
+    }
+
+    let signal = match pick {
+        Some(pick) => {
+            let counts: Vec<i64> = results.iter().map(|result| result.0).collect();
+            detector.stands_out(&counts, pick.idx)
+                && floor.map_or(true, |floor| floor.is_signal(&counts, pick.idx))
+        }
+        None => false,
+    };
+
+    // Track the minimum for stats later
+    let min = results
+        .iter()
+        .map(|result| result.0 as u64)
+        .min()
+        .unwrap_or(std::i64::MAX as u64);
+    terminal.update(
+        Box::new(results.clone()),
+        min,
+        pick.map(|pick| pick.confidence),
+        &measurements,
+    );
+
+    terminal.wait();
+
+    Ok(Round {
+        results,
+        pick,
+        extra_runs,
+        signal,
+    })
+}
+
+/// Run `input` `runs` times with `counter` to measure the noise of the
+/// values outliers are searched in
+pub fn calibrate<P: AsRef<Path>>(
+    path: P,
+    runs: u32,
+    counter: &dyn InstCounter,
+    selector: &Selector,
+    input: &Input,
+    timeout: Duration,
+    vars: HashMap<String, String>,
+    drop_ptrace: bool,
+) -> Result<Option<NoiseFloor>, SolverError> {
+    let pool = Pool::new(num_cpus::get());
+
+    let data = (0..runs).map(|run| (run, input.clone())).collect();
+    let (inputs, samples): (Vec<_>, Vec<_>) = measure(
+        path.as_ref(),
+        1,
+        data,
+        counter,
+        &vars,
+        timeout,
+        drop_ptrace,
+        &pool,
+    )
+    .into_iter()
+    .map(|(inp_pair, measurement)| (inp_pair, vec![measurement]))
+    .unzip();
+    let (results, _) = reduce(&inputs, &samples, counter, selector)?;
+
+    let counts: Vec<i64> = results.iter().map(|result| result.0).collect();
+    Ok(NoiseFloor::from_counts(&counts))
+}
+
+/// Run each input `repeat` times with `counter`, keeping the measurement of
+/// the last run. Inputs that fail to run are left out.
+fn measure<T: Debug + Send>(
+    path: &Path,
+    repeat: u32,
+    data: Vec<(T, Input)>,
+    counter: &dyn InstCounter,
+    vars: &HashMap<String, String>,
+    timeout: Duration,
+    drop_ptrace: bool,
+    pool: &Pool,
+) -> Vec<((T, Input), Measurement)> {
+    // Number of threads to spawn
+    let mut num_jobs: i64 = 0;
+
+    let (tx, rx) = channel();
+
+    let mut results = Vec::with_capacity(data.len());
+    let counter = Arc::new(counter);
+
+    pool.scoped(|scope| {
+        for inp_pair in data {
+            num_jobs += 1;
+            let tx = tx.clone();
+            let test = path.to_path_buf();
+            // give it to a thread to handle
+            let vars = vars.clone();
+            let counter = counter.clone();
+
+            scope.execute(move || {
+                // print out inp variable at the trace level
+                let inp = (inp_pair.1).clone();
+                let data = InstCountData {
+                    path: test,
+                    inp,
+                    vars,
+                    timeout,
+                    drop_ptrace,
+                };
+                let mut inst_count = counter.get_inst_count(&data);
+                trace!("inst_count: {:?}", inst_count);
+                for _ in 1..repeat {
+                    inst_count = counter.get_inst_count(&data);
+                    trace!("inst_count: {:?}", inst_count);
                 }
+                let _ = tx.send((inst_count, (inp_pair)));
+            });
+        }
+    });
+    // Get results from the threads
+    for _ in 0..num_jobs {
+        let tmp = rx.recv().unwrap();
+        match tmp.0 {
+            Ok(x) => results.push((tmp.1, x)),
+            Err(x) => {
+                warn!("{:?} \n returned: {:?}", (tmp.1).0, x);
+                continue;
             }
         }
-        results.shrink_to_fit();
-        terminal.update(results.clone(), min);
+    }
+    results.shrink_to_fit();
+    results
+}
 
-        terminal.wait();
+/// Reduce the runs of each input to the mean of the values outliers are
+/// searched in. Also returns the first run of each input, adjusted by
+/// `counter` along with the other runs of the round.
+fn reduce(
+    inputs: &[(GenItem, Input)],
+    samples: &[Vec<Measurement>],
+    counter: &dyn InstCounter,
+    selector: &Selector,
+) -> Result<(Vec<(i64, (GenItem, Input))>, Vec<Measurement>), SolverError> {
+    let mut runs: Vec<Measurement> = samples.iter().flatten().cloned().collect();
+    counter.finish_round(&mut runs);
 
-        // inform generator of the result
-        if results.is_empty() {
-            warn!("Results empty {:?}", results);
-            return Err(SolverError::new(Runner::Unknown, "No valid results found"));
-        }
-        let good_idx = statistics::find_outlier(results.as_slice());
-        if !gen.update((good_idx.1).0) {
-            break Ok((good_idx.1).1.clone());
+    let mut runs = runs.into_iter();
+    let mut results = Vec::with_capacity(inputs.len());
+    let mut measurements = Vec::with_capacity(inputs.len());
+    for (inp_pair, sample) in inputs.iter().zip(samples) {
+        let sample: Vec<Measurement> = runs.by_ref().take(sample.len()).collect();
+        let mut sum = 0;
+        for run in &sample {
+            sum += selector.select(run)?;
         }
+        results.push((sum / sample.len() as i64, inp_pair.clone()));
+        measurements.push(sample[0].clone());
     }
+    Ok((results, measurements))
 }
@@ -0,0 +1,160 @@
+use crate::errors::*;
+
+/// Ordered set of candidate bytes a generator tries for each position
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Charset {
+    chars: Vec<u8>,
+}
+
+impl Charset {
+    /// Charset of `chars` in order, without duplicates
+    pub fn new(chars: &[u8]) -> Charset {
+        let mut unique = Vec::with_capacity(chars.len());
+        for &c in chars {
+            if !unique.contains(&c) {
+                unique.push(c);
+            }
+        }
+        Charset { chars: unique }
+    }
+
+    /// Every byte from `min` to `max`
+    pub fn range(min: u8, max: u8) -> Charset {
+        Charset {
+            chars: (min..=max).collect(),
+        }
+    }
+
+    /// Printable ASCII, 0x20-0x7e
+    pub fn printable() -> Charset {
+        Charset::range(0x20, 0x7e)
+    }
+
+    /// Every byte, 0x00-0xff
+    pub fn bytes() -> Charset {
+        Charset::range(0x00, 0xff)
+    }
+
+    /// Lowercase hex digits
+    pub fn hex() -> Charset {
+        Charset::new(b"0123456789abcdef")
+    }
+
+    /// Digits and ASCII letters
+    pub fn alnum() -> Charset {
+        Charset::new(b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz")
+    }
+
+    /// Parse a charset from an argument, either one of the names `hex`,
+    /// `alnum`, `printable` and `bytes`, or a custom set like:
+    ///
+    /// ``` text
+    /// a-z0-9_{}
+    /// ```
+    ///
+    /// `x-y` is every byte from `x` to `y`. `\-`, `\\` and `\xHH` are a
+    /// literal `-`, a backslash and the byte `HH`.
+    pub fn parse_from_arg(arg: &str) -> SolverResult<Self> {
+        debug!("Executing parse_from_arg:");
+        match arg {
+            "hex" => return Ok(Charset::hex()),
+            "alnum" => return Ok(Charset::alnum()),
+            "printable" => return Ok(Charset::printable()),
+            "bytes" => return Ok(Charset::bytes()),
+            _ => {}
+        }
+
+        let invalid = || SolverError::new(Runner::ArgError, &format!("Invalid charset {}", arg));
+
+        // Resolve escapes first, remembering which bytes were escaped so an
+        // escaped `-` is not taken for a range
+        let mut bytes = Vec::new();
+        let mut iter = arg.bytes();
+        while let Some(b) = iter.next() {
+            if b != b'\\' {
+                bytes.push((b, false));
+                continue;
+            }
+            let escaped = match iter.next() {
+                Some(b'x') => {
+                    let hex: Vec<u8> = iter.by_ref().take(2).collect();
+                    let hex = std::str::from_utf8(&hex).map_err(|_| invalid())?;
+                    u8::from_str_radix(hex, 0x10).map_err(|_| invalid())?
+                }
+                Some(b) => b,
+                None => return Err(invalid()),
+            };
+            bytes.push((escaped, true));
+        }
+
+        let mut chars = Vec::new();
+        let mut idx = 0;
+        while idx < bytes.len() {
+            match bytes.get(idx + 1..idx + 3) {
+                Some(&[(b'-', false), (max, _)]) => {
+                    let min = bytes[idx].0;
+                    if min > max {
+                        return Err(invalid());
+                    }
+                    chars.extend(min..=max);
+                    idx += 3;
+                }
+                _ => {
+                    chars.push(bytes[idx].0);
+                    idx += 1;
+                }
+            }
+        }
+
+        if chars.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Charset::new(&chars))
+    }
+
+    /// Candidate bytes in order
+    pub fn chars(&self) -> &[u8] {
+        &self.chars
+    }
+
+    /// The candidate at `idx`
+    pub fn get(&self, idx: usize) -> Option<u8> {
+        self.chars.get(idx).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.chars.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+}
+
+impl Default for Charset {
+    /// Printable ASCII
+    fn default() -> Self {
+        Charset::printable()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Charset;
+
+    #[test]
+    fn parse_charsets() {
+        assert_eq!(Charset::parse_from_arg("hex").unwrap(), Charset::hex());
+        assert_eq!(Charset::parse_from_arg("bytes").unwrap().len(), 0x100);
+        assert_eq!(
+            Charset::parse_from_arg("a-c0-2_{}").unwrap().chars(),
+            b"abc012_{}"
+        );
+        assert_eq!(
+            Charset::parse_from_arg("\\-a-c\\x41z-").unwrap().chars(),
+            b"-abcAz"
+        );
+        assert!(Charset::parse_from_arg("z-a").is_err());
+        assert!(Charset::parse_from_arg("").is_err());
+    }
+}
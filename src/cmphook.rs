@@ -0,0 +1,108 @@
+use crate::brute::*;
+use crate::errors::*;
+use crate::measurement::Measurement;
+use crate::process::{PreloadLib, Process, PtraceMode};
+use byteorder::{ByteOrder, NativeEndian};
+use nix::fcntl::OFlag;
+use nix::unistd::{close, pipe2, read};
+use std::os::unix::io::RawFd;
+
+/// Shared object preloaded into the target, built from `cmphook/cmphook.c`
+static CMPHOOK_LIB: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/libb7cmphook.so"));
+
+/// Measures progress by hooking `strcmp`, `strncmp` and `memcmp` with
+/// `LD_PRELOAD`, for targets that compare the input with libc where
+/// instruction counts barely move. The hook reports the longest matching
+/// prefix of any comparison, so it only works on dynamically linked targets
+/// of the same architecture as b7.
+pub struct CmpHookSolver {
+    lib: PreloadLib,
+}
+
+/// Both ends of the pipe the hook reports over, closed on drop
+struct ReportPipe {
+    read: RawFd,
+    write: RawFd,
+}
+
+impl ReportPipe {
+    fn new() -> SolverResult<ReportPipe> {
+        let (read, write) = pipe2(OFlag::O_CLOEXEC | OFlag::O_NONBLOCK)?;
+        Ok(ReportPipe { read, write })
+    }
+
+    /// Read the longest prefix that was reported, without blocking
+    fn longest_prefix(&self) -> SolverResult<u64> {
+        let mut reports = Vec::new();
+        let mut buf = [0; 512];
+        loop {
+            match read(self.read, &mut buf) {
+                Ok(0) => break,
+                Ok(n) => reports.extend_from_slice(&buf[..n]),
+                Err(nix::Error::Sys(nix::errno::Errno::EAGAIN)) => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Ok(reports
+            .chunks_exact(8)
+            .map(NativeEndian::read_u64)
+            .max()
+            .unwrap_or(0))
+    }
+}
+
+impl Drop for ReportPipe {
+    fn drop(&mut self) {
+        let _ = close(self.read);
+        let _ = close(self.write);
+    }
+}
+
+impl CmpHookSolver {
+    pub fn new() -> CmpHookSolver {
+        CmpHookSolver {
+            lib: PreloadLib::new("b7-cmphook", "libb7cmphook.so", CMPHOOK_LIB),
+        }
+    }
+}
+
+impl Default for CmpHookSolver {
+    fn default() -> Self {
+        CmpHookSolver::new()
+    }
+}
+
+impl InstCounter for CmpHookSolver {
+    /// runs the process with the comparison hook preloaded
+    ///
+    /// # Return
+    /// * longest matching prefix of a comparison or error
+    fn get_inst_count(&self, data: &InstCountData) -> Result<Measurement, SolverError> {
+        debug!("Executing get_inst_count:");
+        let pipe = ReportPipe::new()?;
+
+        let mut process = Process::new(&data.path)?;
+        process.input(&data.inp)?;
+        process.env("LD_PRELOAD", self.lib.path()?);
+        process.env("B7_CMPHOOK_FD", pipe.write.to_string());
+        process.inherit_fd(pipe.write);
+
+        let ptrace_mode = if data.drop_ptrace {
+            PtraceMode::Drop
+        } else {
+            PtraceMode::Always
+        };
+        process.with_ptrace_mode(ptrace_mode);
+
+        let mut handle = process.spawn();
+        let report = handle.finish(data.timeout)?;
+
+        let prefix = pipe.longest_prefix()?;
+        trace!("longest matching prefix: {}", prefix);
+
+        Ok(report
+            .measurement(prefix as i64)
+            .with("prefix", prefix as i64))
+    }
+}
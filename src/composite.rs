@@ -0,0 +1,315 @@
+use crate::brute::*;
+use crate::errors::*;
+use crate::measurement::Measurement;
+
+/// How the results of the solvers in a `CompositeSolver` are combined
+#[derive(Debug, Clone, PartialEq)]
+pub enum Combine {
+    /// Sum of the results, each multiplied by its weight
+    WeightedSum(Vec<i64>),
+    /// Order by the first solver, breaking ties with the following ones
+    Lexicographic,
+    /// Use the first solver whose results differ within a round
+    FirstSignal,
+}
+
+impl Combine {
+    /// Parse a combination from a `sum`, `lex` or `first` arg. `sum` takes
+    /// comma separated weights, one per solver (default 1).
+    ///
+    /// Example: `sum=1,1000`
+    pub fn parse_from_arg(arg: &str) -> SolverResult<Self> {
+        debug!("Executing parse_from_arg:");
+        let mut parts = arg.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some("sum"), None) => Ok(Combine::WeightedSum(Vec::new())),
+            (Some("sum"), Some(weights)) => weights
+                .split(',')
+                .map(|weight| {
+                    weight.parse().map_err(|_| {
+                        SolverError::new(Runner::ArgError, &format!("Invalid weight {}", weight))
+                    })
+                })
+                .collect::<SolverResult<Vec<i64>>>()
+                .map(Combine::WeightedSum),
+            (Some("lex"), None) => Ok(Combine::Lexicographic),
+            (Some("first"), None) => Ok(Combine::FirstSignal),
+            _ => Err(SolverError::new(
+                Runner::ArgError,
+                "Combination must be sum[=weights], lex or first",
+            )),
+        }
+    }
+}
+
+impl Default for Combine {
+    fn default() -> Self {
+        Combine::Lexicographic
+    }
+}
+
+/// Runs several solvers on every input and combines their results, e.g. to
+/// use the syscall count first and instructions as the tiebreaker. The
+/// progress of solver `i` is kept as the metric `progress<i>`, next to the
+/// metrics of every solver.
+///
+/// Lexicographic and first signal combinations are resolved per round, once
+/// the results of every input are known. Until then the progress is the one
+/// of the first solver.
+///
+/// Lexicographic progress keeps the units of the solvers: it is how far each
+/// result is above the smallest of its round, with every solver given
+/// `62 / n` bits and the first solver in the highest. Noise of a tiebreaker
+/// can't hide a difference in the solvers before it. Spreads that don't fit
+/// in a round saturate.
+pub struct CompositeSolver {
+    solvers: Vec<Box<dyn InstCounter>>,
+    combine: Combine,
+}
+
+impl CompositeSolver {
+    pub fn new(solvers: Vec<Box<dyn InstCounter>>, combine: Combine) -> SolverResult<Self> {
+        if solvers.is_empty() {
+            return Err(SolverError::new(
+                Runner::ArgError,
+                "A composite solver needs at least one solver",
+            ));
+        }
+        if let Combine::WeightedSum(weights) = &combine {
+            if !weights.is_empty() && weights.len() != solvers.len() {
+                return Err(SolverError::new(
+                    Runner::ArgError,
+                    "There must be one weight per solver",
+                ));
+            }
+        }
+
+        Ok(CompositeSolver { solvers, combine })
+    }
+
+    /// Progress of every solver in `measurement`
+    fn counts(&self, measurement: &Measurement) -> Vec<i64> {
+        (0..self.solvers.len())
+            .map(|idx| measurement.get(&progress_metric(idx)).unwrap_or(0))
+            .collect()
+    }
+
+    /// Lexicographic progress of each of the `counts` of a round
+    fn lexicographic(&self, counts: &[Vec<i64>]) -> Vec<i64> {
+        let bits = 62 / self.solvers.len() as u32;
+        let max = (1i64 << bits) - 1;
+        let mins: Vec<i64> = (0..self.solvers.len())
+            .map(|idx| counts.iter().map(|count| count[idx]).min().unwrap_or(0))
+            .collect();
+
+        counts
+            .iter()
+            .map(|count| {
+                count.iter().zip(&mins).fold(0, |packed, (count, min)| {
+                    let offset = count.saturating_sub(*min);
+                    if offset > max {
+                        warn!("Composite results spread over more than {} bits", bits);
+                    }
+                    (packed << bits) | offset.min(max)
+                })
+            })
+            .collect()
+    }
+}
+
+/// Name of the metric holding the progress of solver `idx`
+fn progress_metric(idx: usize) -> String {
+    format!("progress{}", idx)
+}
+
+impl InstCounter for CompositeSolver {
+    /// runs the process under every solver
+    ///
+    /// # Return
+    /// * combined result of the solvers or the first error
+    fn get_inst_count(&self, data: &InstCountData) -> Result<Measurement, SolverError> {
+        debug!("Executing get_inst_count:");
+        let measurements = self
+            .solvers
+            .iter()
+            .map(|solver| solver.get_inst_count(data))
+            .collect::<SolverResult<Vec<Measurement>>>()?;
+        let counts: Vec<i64> = measurements.iter().map(|m| m.progress).collect();
+        trace!("composite counts: {:?}", counts);
+
+        let progress = match &self.combine {
+            Combine::WeightedSum(weights) if weights.is_empty() => counts
+                .iter()
+                .fold(0i64, |sum, count| sum.saturating_add(*count)),
+            Combine::WeightedSum(weights) => {
+                counts.iter().zip(weights).fold(0i64, |sum, (count, w)| {
+                    sum.saturating_add(count.saturating_mul(*w))
+                })
+            }
+            Combine::Lexicographic | Combine::FirstSignal => counts[0],
+        };
+
+        let mut combined = Measurement::new(progress);
+        for (idx, count) in counts.iter().enumerate() {
+            combined.set(&progress_metric(idx), *count);
+        }
+        for measurement in &measurements {
+            combined.merge(measurement);
+        }
+
+        Ok(combined)
+    }
+
+    /// replaces the progress with the lexicographic progress, or with the
+    /// progress of the first solver that shows a signal
+    fn finish_round(&self, measurements: &mut [Measurement]) {
+        debug!("Executing finish_round:");
+        if measurements.is_empty() {
+            return;
+        }
+        let counts: Vec<Vec<i64>> = measurements.iter().map(|m| self.counts(m)).collect();
+
+        match self.combine {
+            Combine::WeightedSum(_) => {}
+            Combine::Lexicographic => {
+                let progress = self.lexicographic(&counts);
+                for (measurement, progress) in measurements.iter_mut().zip(progress) {
+                    measurement.progress = progress;
+                }
+            }
+            Combine::FirstSignal => {
+                let idx = (0..self.solvers.len())
+                    .find(|idx| counts.iter().any(|count| count[*idx] != counts[0][*idx]))
+                    .unwrap_or(self.solvers.len() - 1);
+                trace!("solver {} shows a signal", idx);
+
+                for (measurement, count) in measurements.iter_mut().zip(&counts) {
+                    measurement.progress = count[idx];
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Combine, CompositeSolver};
+    use crate::brute::{InstCountData, InstCounter};
+    use crate::errors::*;
+    use crate::generators::Input;
+    use crate::measurement::Measurement;
+    use crate::statistics::{MedianMad, OutlierDetector};
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    struct Fixed(i64);
+
+    impl InstCounter for Fixed {
+        fn get_inst_count(&self, _data: &InstCountData) -> Result<Measurement, SolverError> {
+            Ok(Measurement::new(self.0))
+        }
+    }
+
+    fn composite(combine: Combine) -> CompositeSolver {
+        CompositeSolver::new(vec![Box::new(Fixed(0)), Box::new(Fixed(0))], combine).unwrap()
+    }
+
+    fn data() -> InstCountData {
+        InstCountData {
+            path: PathBuf::new(),
+            inp: Input::default(),
+            vars: HashMap::new(),
+            timeout: Duration::from_secs(1),
+            drop_ptrace: false,
+        }
+    }
+
+    #[test]
+    fn parse_combine() {
+        assert_eq!(
+            Combine::parse_from_arg("sum=1,-2").unwrap(),
+            Combine::WeightedSum(vec![1, -2])
+        );
+        assert_eq!(
+            Combine::parse_from_arg("first").unwrap(),
+            Combine::FirstSignal
+        );
+        assert!(Combine::parse_from_arg("lex=1").is_err());
+    }
+
+    #[test]
+    fn lexicographic_order() {
+        let solver = composite(Combine::Lexicographic);
+        let mut measurements = vec![
+            Measurement::new(0)
+                .with("progress0", 2)
+                .with("progress1", 0),
+            Measurement::new(0)
+                .with("progress0", 1)
+                .with("progress1", i64::max_value()),
+            Measurement::new(0)
+                .with("progress0", 2)
+                .with("progress1", -1),
+            Measurement::new(0)
+                .with("progress0", 1)
+                .with("progress1", i64::max_value()),
+        ];
+        solver.finish_round(&mut measurements);
+        let progress: Vec<i64> = measurements.iter().map(|m| m.progress).collect();
+        assert!(progress[0] > progress[2]);
+        assert!(progress[2] > progress[1]);
+        assert_eq!(progress[1], progress[3]);
+    }
+
+    #[test]
+    fn lexicographic_noisy_tiebreaker() {
+        // One more syscall, with fewer instructions than most of the others
+        let solver = composite(Combine::Lexicographic);
+        let instructions = [
+            1_000_450, 1_000_020, 1_000_910, 1_000_300, 1_000_770, 1_000_130,
+        ];
+        let mut measurements: Vec<Measurement> = instructions
+            .iter()
+            .enumerate()
+            .map(|(idx, count)| {
+                Measurement::new(0)
+                    .with("progress0", if idx == 3 { 13 } else { 12 })
+                    .with("progress1", *count)
+            })
+            .collect();
+        solver.finish_round(&mut measurements);
+
+        let counts: Vec<i64> = measurements.iter().map(|m| m.progress).collect();
+        let pick = MedianMad.pick(&counts).unwrap();
+        assert_eq!(pick.idx, 3);
+        assert!(MedianMad.stands_out(&counts, pick.idx));
+    }
+
+    #[test]
+    fn weighted_sum_saturates() {
+        let solver = CompositeSolver::new(
+            vec![Box::new(Fixed(5)), Box::new(Fixed(2))],
+            Combine::WeightedSum(vec![1, i64::max_value()]),
+        )
+        .unwrap();
+        let measurement = solver.get_inst_count(&data()).unwrap();
+        assert_eq!(measurement.progress, i64::max_value());
+    }
+
+    #[test]
+    fn first_signal() {
+        let solver = composite(Combine::FirstSignal);
+        let mut measurements = vec![
+            Measurement::new(0)
+                .with("progress0", 4)
+                .with("progress1", 10),
+            Measurement::new(0)
+                .with("progress0", 4)
+                .with("progress1", 12),
+        ];
+        solver.finish_round(&mut measurements);
+        assert_eq!(measurements[0].progress, 10);
+        assert_eq!(measurements[1].progress, 12);
+    }
+}
@@ -0,0 +1,251 @@
+use crate::errors::*;
+use nix::errno::Errno;
+use nix::poll::{poll, EventFlags, PollFd};
+use regex::bytes::Regex;
+use std::fs;
+use std::io::{Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::process::{ChildStdin, ChildStdout};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+/// Milliseconds a read waits for output before checking if the target exited
+const POLL_INTERVAL: i32 = 10;
+
+/// One step of a conversation with the target
+#[derive(Debug, Clone)]
+pub enum Step {
+    /// Wait until stdout matches a regex
+    Expect(Regex),
+    /// Send fixed bytes
+    Send(Vec<u8>),
+    /// Send the brute forced stdin input
+    Input,
+}
+
+/// A script of prompts to wait for and answers to send, for targets that
+/// read their input interactively. The brute forced stdin is sent at the
+/// `Input` step, and the rest is replayed for every candidate. stdin stays
+/// open until the last step.
+#[derive(Debug, Clone)]
+pub struct Conversation {
+    steps: Vec<Step>,
+}
+
+/// Turn `\n`, `\r`, `\t`, `\\` and `\xHH` escapes into bytes
+fn unescape(text: &str) -> SolverResult<Vec<u8>> {
+    let invalid = || SolverError::new(Runner::ArgError, &format!("Invalid escape in {}", text));
+    let mut bytes = Vec::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => bytes.push(b'\n'),
+            Some('r') => bytes.push(b'\r'),
+            Some('t') => bytes.push(b'\t'),
+            Some('\\') => bytes.push(b'\\'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                bytes.push(u8::from_str_radix(&hex, 0x10).map_err(|_| invalid())?);
+            }
+            _ => return Err(invalid()),
+        }
+    }
+
+    Ok(bytes)
+}
+
+impl Conversation {
+    /// Parse a conversation from a script with one step per line:
+    ///
+    /// ``` text
+    /// expect Choice:
+    /// send 2\n
+    /// expect Password:
+    /// input
+    /// send \n
+    /// ```
+    ///
+    /// `expect` takes a regex, `send` takes text with `\n`, `\t` and `\xHH`
+    /// escapes. Empty lines and lines starting with `#` are skipped.
+    pub fn parse(script: &str) -> SolverResult<Self> {
+        debug!("Executing Conversation::parse:");
+        let mut steps = Vec::new();
+        for line in script.lines() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = line.splitn(2, ' ');
+            let step = match (parts.next(), parts.next()) {
+                (Some("expect"), Some(regex)) => Step::Expect(Regex::new(regex).map_err(|_| {
+                    SolverError::new(Runner::ArgError, &format!("Invalid expect regex {}", regex))
+                })?),
+                (Some("send"), Some(text)) => Step::Send(unescape(text)?),
+                (Some("input"), None) => Step::Input,
+                _ => {
+                    return Err(SolverError::new(
+                        Runner::ArgError,
+                        &format!("Invalid conversation step {}", line),
+                    ))
+                }
+            };
+            steps.push(step);
+        }
+
+        let inputs = steps.iter().filter(|step| match step {
+            Step::Input => true,
+            _ => false,
+        });
+        if inputs.count() != 1 {
+            return Err(SolverError::new(
+                Runner::ArgError,
+                "A conversation needs exactly one input step",
+            ));
+        }
+
+        Ok(Conversation { steps })
+    }
+
+    /// Read and parse a conversation script
+    pub fn from_file(path: &Path) -> SolverResult<Self> {
+        Conversation::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Hold the conversation with `input` sent at the input step, in a
+    /// thread that returns all stdout once the target closed it or exited
+    pub(crate) fn start(
+        &self,
+        input: Vec<u8>,
+        mut stdin: ChildStdin,
+        mut stdout: ChildStdout,
+    ) -> ConversationThread {
+        debug!("Executing Conversation::start:");
+        let steps = self.steps.clone();
+        let exited = Arc::new(AtomicBool::new(false));
+        let done = exited.clone();
+        let thread = thread::spawn(move || {
+            let mut output = Vec::new();
+            // Where the output the next expect searches starts
+            let mut unmatched = 0;
+            let mut buf = [0; 0x1000];
+
+            for step in &steps {
+                let sent = match step {
+                    Step::Expect(regex) => loop {
+                        if let Some(found) = regex.find(&output[unmatched..]) {
+                            unmatched += found.end();
+                            break Ok(());
+                        }
+                        match read_output(&mut stdout, &mut buf, &done) {
+                            0 => return output,
+                            read => output.extend_from_slice(&buf[..read]),
+                        }
+                    },
+                    Step::Send(bytes) => stdin.write_all(bytes),
+                    Step::Input => stdin.write_all(&input),
+                };
+                if sent.is_err() {
+                    break;
+                }
+            }
+
+            // Close stdin and collect the rest of the output
+            drop(stdin);
+            loop {
+                match read_output(&mut stdout, &mut buf, &done) {
+                    0 => return output,
+                    read => output.extend_from_slice(&buf[..read]),
+                }
+            }
+        });
+
+        ConversationThread { thread, exited }
+    }
+}
+
+/// Read from the target's stdout into `buf`. Returns 0 at the end of the
+/// output, which is also reached once the target exited and nothing is
+/// left to read, even if another process still holds stdout open.
+fn read_output(stdout: &mut ChildStdout, buf: &mut [u8], exited: &AtomicBool) -> usize {
+    loop {
+        // Checked before polling, so output written before the exit is read
+        let exited = exited.load(Ordering::SeqCst);
+        let mut fds = [PollFd::new(stdout.as_raw_fd(), EventFlags::POLLIN)];
+        match poll(&mut fds, POLL_INTERVAL) {
+            Ok(0) if exited => return 0,
+            Ok(0) | Err(nix::Error::Sys(Errno::EINTR)) => continue,
+            Ok(_) => return stdout.read(buf).unwrap_or(0),
+            Err(_) => return 0,
+        }
+    }
+}
+
+/// A conversation held in a thread, returned by `Conversation::start`
+#[derive(Debug)]
+pub(crate) struct ConversationThread {
+    thread: JoinHandle<Vec<u8>>,
+    /// Set once the target exited
+    exited: Arc<AtomicBool>,
+}
+
+impl ConversationThread {
+    /// Wait until the thread read the output the target left, and return
+    /// all stdout. Must only be called after the target exited.
+    pub(crate) fn finish(self) -> Vec<u8> {
+        self.exited.store(true, Ordering::SeqCst);
+        self.thread.join().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Conversation, Step};
+    use std::process::{Command, Stdio};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn parse_steps() {
+        let conversation =
+            Conversation::parse("# login\nexpect Password:\ninput\n\nsend \\x00\\n\n").unwrap();
+        match &conversation.steps[..] {
+            [Step::Expect(regex), Step::Input, Step::Send(bytes)] => {
+                assert_eq!(regex.as_str(), "Password:");
+                assert_eq!(bytes, b"\x00\n");
+            }
+            steps => panic!("unexpected steps {:?}", steps),
+        }
+        assert!(Conversation::parse("expect a\nsend b\n").is_err());
+        assert!(Conversation::parse("input\ninput\n").is_err());
+        assert!(Conversation::parse("wait 1\ninput\n").is_err());
+    }
+
+    #[test]
+    fn output_held_open_after_exit() {
+        let mut child = Command::new("/bin/sh")
+            .args(&["-c", "read line; echo \"$line\"; sleep 5 &"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let conversation = Conversation::parse("input\n").unwrap();
+        let thread = conversation.start(
+            b"hi\n".to_vec(),
+            child.stdin.take().unwrap(),
+            child.stdout.take().unwrap(),
+        );
+        child.wait().unwrap();
+
+        // The backgrounded sleep keeps stdout open
+        let start = Instant::now();
+        assert_eq!(thread.finish(), b"hi\n");
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,88 @@
+use crate::binary::Binary;
+use crate::brute::*;
+use crate::errors::*;
+use crate::measurement::Measurement;
+use crate::process::{Coverage, Process, PtraceMode};
+use crate::IS_X86;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Counts the unique basic blocks, or edges between them, that the target
+/// reaches. Blocks are found by disassembling `.text` and are marked with
+/// breakpoints, so this only works on x86.
+pub struct CoverageSolver {
+    mode: Coverage,
+    /// Basic blocks of the last binary that was run
+    blocks: Mutex<Option<(PathBuf, Arc<Vec<usize>>)>>,
+}
+
+impl CoverageSolver {
+    pub fn new(mode: Coverage) -> CoverageSolver {
+        CoverageSolver {
+            mode,
+            blocks: Mutex::new(None),
+        }
+    }
+
+    /// Get the basic blocks of a binary, disassembling it only the first time
+    fn get_blocks(&self, path: &Path) -> Result<Arc<Vec<usize>>, SolverError> {
+        debug!("Executing get_blocks:");
+        let mut cache = self.blocks.lock().unwrap();
+        if let Some((cached_path, blocks)) = &*cache {
+            if cached_path == path {
+                return Ok(blocks.clone());
+            }
+        }
+
+        let blocks = Arc::new(Binary::new(path)?.basic_blocks()?);
+        info!("found {} basic blocks", blocks.len());
+        *cache = Some((path.to_path_buf(), blocks.clone()));
+
+        Ok(blocks)
+    }
+}
+
+impl InstCounter for CoverageSolver {
+    /// runs the process under ptrace with a breakpoint on every basic block
+    ///
+    /// # Return
+    /// * number of unique blocks or edges that were reached or error
+    fn get_inst_count(&self, data: &InstCountData) -> Result<Measurement, SolverError> {
+        debug!("Executing get_inst_count:");
+        if !IS_X86 {
+            return Err(SolverError::new(
+                Runner::ArgError,
+                "Breakpoints only work on x86",
+            ));
+        }
+        if data.drop_ptrace {
+            return Err(SolverError::new(
+                Runner::ArgError,
+                "ptrace dropping and coverage are mutually exclusive",
+            ));
+        }
+
+        let mut process = Process::new(&data.path)?;
+        process.input(&data.inp)?;
+        process.with_ptrace_mode(PtraceMode::Always);
+        process.coverage(self.mode, self.get_blocks(&data.path)?);
+
+        let mut handle = process.spawn();
+        let report = handle.finish(data.timeout)?;
+        trace!(
+            "reached {} blocks and {} edges",
+            report.blocks,
+            report.edges
+        );
+
+        let progress = match self.mode {
+            Coverage::Blocks => report.blocks,
+            Coverage::Edges => report.edges,
+        };
+
+        Ok(report
+            .measurement(progress as i64)
+            .with("blocks", report.blocks as i64)
+            .with("edges", report.edges as i64))
+    }
+}
@@ -1,6 +1,7 @@
 #![cfg(feature = "dynamorio")]
 use crate::brute::*;
 use crate::errors::*;
+use crate::measurement::Measurement;
 use crate::process::Process;
 use std::ffi::OsStr;
 use std::fs::File;
@@ -40,7 +41,7 @@ impl DynamorioSolver {
 impl InstCounter for DynamorioSolver {
     /// Handles basic proc spawning and running under dynamorio
     /// only works on 64 bit for now
-    fn get_inst_count(&self, data: &InstCountData) -> Result<i64, SolverError> {
+    fn get_inst_count(&self, data: &InstCountData) -> Result<Measurement, SolverError> {
         debug!("Executing get_inst_count:");
         let dynpath = PathBuf::from(data.vars.get("dynpath").unwrap());
 
@@ -67,17 +68,17 @@ impl InstCounter for DynamorioSolver {
         proccess.arg(libinscount);
         proccess.arg("--");
         proccess.arg(&data.path);
-        if let Some(argv) = &data.inp.argv {
-            for arg in argv {
-                proccess.arg(OsStr::from_bytes(arg));
+        proccess.input_args(&data.inp)?;
+        proccess.input_stdin(&data.inp);
+        // drrun passes its environment on to the target
+        if let Some(env) = &data.inp.env {
+            for (name, value) in env {
+                proccess.env(OsStr::from_bytes(name), OsStr::from_bytes(value));
             }
         }
-        if let Some(stdin) = &data.inp.stdin {
-            proccess.stdin_input(stdin.clone());
-        }
 
         let mut handle = proccess.spawn();
-        handle.finish(data.timeout)?;
+        let report = handle.finish(data.timeout)?;
 
         let mut buf: Vec<u8> = Vec::new();
         handle.read_stdout(&mut buf)?;
@@ -98,6 +99,6 @@ impl InstCounter for DynamorioSolver {
         let cap = &caps[caps.len() - 1];
         let num2: i64 = cap.parse().unwrap();
 
-        Ok(num2)
+        Ok(report.measurement(num2).with("instructions", num2))
     }
 }
@@ -0,0 +1,345 @@
+use crate::errors::*;
+use crate::generators::Input;
+use crate::process::{FixedEnv, PreloadLib};
+use byteorder::{ByteOrder, NativeEndian, WriteBytesExt};
+use nix::fcntl::{fcntl, FcntlArg, FdFlag, OFlag};
+use nix::poll::{poll, EventFlags, PollFd};
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::{close, pipe2, Pid};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Shared object preloaded into the target, built from `forkserver/forkserver.c`
+static FORKSERVER_LIB: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/libb7forkserver.so"));
+
+/// Where the forkserver forks the children that run the inputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForkPoint {
+    /// At `main`, after dynamic loading and libc initialization
+    Main,
+    /// When execution first reaches an address. For PIE binaries, the address
+    /// is relative to the executable base. argv is already set by then, so
+    /// only stdin and memory input can be delivered.
+    Addr(usize),
+}
+
+impl ForkPoint {
+    /// Parse a fork point from `main` or a hex address
+    pub fn parse_from_arg(arg: &str) -> SolverResult<Self> {
+        debug!("Executing parse_from_arg:");
+        if arg == "main" {
+            return Ok(ForkPoint::Main);
+        }
+
+        usize::from_str_radix(arg, 0x10)
+            .map(ForkPoint::Addr)
+            .map_err(|_| SolverError::new(Runner::ArgError, "Invalid forkserver address"))
+    }
+}
+
+/// What happened to a forked child
+#[derive(Debug, Clone)]
+pub struct ForkReport {
+    /// Exit code, or 128 plus the signal that killed the child
+    pub exit_status: i32,
+    /// Time from starting the child until it exited
+    pub wall_time: Duration,
+    /// Bytes the child wrote to stdout
+    pub stdout_len: usize,
+}
+
+/// A target stopped at its fork point that forks a child for every input
+#[derive(Debug)]
+struct ForkServer {
+    path: PathBuf,
+    child: Child,
+    /// Requests and the byte that starts a child
+    ctl: File,
+    /// Pids and exit statuses of the children
+    status: File,
+}
+
+impl ForkServer {
+    /// Start the target with the forkserver preloaded, in the fixed
+    /// environment `env` if there is one
+    fn spawn(
+        path: &Path,
+        point: ForkPoint,
+        lib: &Path,
+        env: Option<&FixedEnv>,
+    ) -> SolverResult<ForkServer> {
+        debug!("Executing ForkServer::spawn:");
+        let (ctl_read, ctl_write) = pipe2(OFlag::O_CLOEXEC)?;
+        let (status_read, status_write) = pipe2(OFlag::O_CLOEXEC)?;
+        let (ctl, status) =
+            unsafe { (File::from_raw_fd(ctl_write), File::from_raw_fd(status_read)) };
+
+        let mut cmd = Command::new(path);
+        // The fixed environment clears the variables, so it goes first
+        if let Some(env) = env {
+            env.apply(&mut cmd);
+        }
+        cmd.env("LD_PRELOAD", lib)
+            .env("B7_FORKSERVER_FD", format!("{},{}", ctl_read, status_write))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        if let ForkPoint::Addr(addr) = point {
+            cmd.env("B7_FORKSERVER_ADDR", format!("{:x}", addr));
+        }
+
+        // Only the target gets the other ends of the pipes
+        unsafe {
+            cmd.pre_exec(move || {
+                for fd in &[ctl_read, status_write] {
+                    fcntl(*fd, FcntlArg::F_SETFD(FdFlag::empty()))
+                        .map_err(|_| io::Error::last_os_error())?;
+                }
+                Ok(())
+            });
+        }
+        let child = cmd.spawn();
+        close(ctl_read)?;
+        close(status_write)?;
+
+        Ok(ForkServer {
+            path: path.to_path_buf(),
+            child: child?,
+            ctl,
+            status,
+        })
+    }
+
+    /// Fill `buf` from the status pipe, failing if that takes until `deadline`
+    fn read_status(&mut self, buf: &mut [u8], deadline: Instant) -> SolverResult<()> {
+        let mut pos = 0;
+        while pos < buf.len() {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(SolverError::new(Runner::Timeout, "child timeout"));
+            }
+            let time_left = (deadline - now).as_millis() as i32;
+            let mut fds = [PollFd::new(self.status.as_raw_fd(), EventFlags::POLLIN)];
+            if poll(&mut fds, time_left.max(1))? == 0 {
+                continue;
+            }
+
+            match self.status.read(&mut buf[pos..])? {
+                0 => {
+                    return Err(SolverError::new(
+                        Runner::RunnerError,
+                        "forkserver exited, is the target dynamically linked and is \
+                         the fork address reached?",
+                    ))
+                }
+                read => pos += read,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fork a child that will run `input` once it is started
+    fn fork(&mut self, input: &Input, deadline: Instant) -> SolverResult<Pid> {
+        debug!("Executing ForkServer::fork:");
+        let mut request = Vec::new();
+        let argv = input.argv.clone().unwrap_or_default();
+        request.write_u32::<NativeEndian>(argv.len() as u32)?;
+        for arg in &argv {
+            request.write_u32::<NativeEndian>(arg.len() as u32)?;
+            request.extend_from_slice(arg);
+        }
+
+        let stdin = input.stdin.clone().unwrap_or_default();
+        request.write_u32::<NativeEndian>(stdin.len() as u32)?;
+        request.extend_from_slice(&stdin);
+
+        let mem = input.mem.clone().unwrap_or_default();
+        request.write_u32::<NativeEndian>(mem.len() as u32)?;
+        for mem in &mem {
+            request.write_u64::<NativeEndian>(mem.addr as u64)?;
+            request.write_u32::<NativeEndian>(mem.bytes.len() as u32)?;
+            request.extend_from_slice(&mem.bytes);
+        }
+        self.ctl.write_all(&request)?;
+
+        let mut pid = [0; 4];
+        self.read_status(&mut pid, deadline)?;
+        match NativeEndian::read_i32(&pid) {
+            -1 => Err(SolverError::new(
+                Runner::RunnerError,
+                "forkserver could not fork",
+            )),
+            pid => Ok(Pid::from_raw(pid)),
+        }
+    }
+
+    /// Start a forked child and wait until it exits
+    fn run(&mut self, pid: Pid, deadline: Instant) -> SolverResult<ForkReport> {
+        debug!("Executing ForkServer::run:");
+        let start = Instant::now();
+        self.ctl.write_all(&[0])?;
+
+        let mut reply = [0; 12];
+        if let Err(e) = self.read_status(&mut reply, deadline) {
+            let _ = kill(pid, Signal::SIGKILL);
+            return Err(e);
+        }
+        let wall_time = start.elapsed();
+
+        // Decode the wait status
+        let status = NativeEndian::read_i32(&reply[..4]);
+        let exit_status = match status & 0x7f {
+            0 => (status >> 8) & 0xff,
+            signal => 128 + signal,
+        };
+
+        Ok(ForkReport {
+            exit_status,
+            wall_time,
+            stdout_len: NativeEndian::read_u64(&reply[4..]) as usize,
+        })
+    }
+}
+
+impl Drop for ForkServer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+    }
+}
+
+/// Forkservers for running inputs in parallel. Servers are started the first
+/// time they are needed and reused afterwards.
+#[derive(Debug)]
+pub struct ForkServerPool {
+    point: ForkPoint,
+    lib: PreloadLib,
+    /// Servers that are not running an input
+    servers: Mutex<Vec<ForkServer>>,
+}
+
+impl ForkServerPool {
+    pub fn new(point: ForkPoint) -> ForkServerPool {
+        ForkServerPool {
+            point,
+            lib: PreloadLib::new("b7-forkserver", "libb7forkserver.so", FORKSERVER_LIB),
+            servers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Take an idle server of the binary at `path`, or start a new one
+    fn take(&self, path: &Path, env: Option<&FixedEnv>) -> SolverResult<ForkServer> {
+        let mut servers = self.servers.lock().unwrap();
+        match servers.iter().position(|server| server.path == path) {
+            Some(idx) => Ok(servers.swap_remove(idx)),
+            None => {
+                drop(servers);
+                ForkServer::spawn(path, self.point, &self.lib.path()?, env)
+            }
+        }
+    }
+
+    /// run `input` in a child forked from the binary at `path`, started in
+    /// the fixed environment `env` if there is one. `attach` is called with
+    /// the child's pid before it starts, to set up counters.
+    ///
+    /// NOTE: The children are not traced, so memory input is written at the
+    /// fork point and can't wait for a breakpoint
+    pub fn run<T, F>(
+        &self,
+        path: &Path,
+        env: Option<&FixedEnv>,
+        input: &Input,
+        timeout: Duration,
+        attach: F,
+    ) -> SolverResult<(T, ForkReport)>
+    where
+        F: FnOnce(Pid) -> SolverResult<T>,
+    {
+        debug!("Executing ForkServerPool::run:");
+        if let ForkPoint::Addr(_) = self.point {
+            if input.argv.as_ref().map_or(false, |argv| !argv.is_empty()) {
+                return Err(SolverError::new(
+                    Runner::ArgError,
+                    "argv input needs the forkserver to fork at main",
+                ));
+            }
+        }
+        if input.env.is_some()
+            || input.files.is_some()
+            || input.socket.is_some()
+            || input.conversation.is_some()
+        {
+            return Err(SolverError::new(
+                Runner::ArgError,
+                "Environment variable, file, socket and conversation input need the \
+                 target spawned for each input",
+            ));
+        }
+        if let Some(mem) = &input.mem {
+            if mem.iter().any(|mem| mem.breakpoint.is_some()) {
+                return Err(SolverError::new(
+                    Runner::ArgError,
+                    "The forkserver can't wait for memory input breakpoints",
+                ));
+            }
+        }
+        if input.reg.is_some() {
+            return Err(SolverError::new(
+                Runner::ArgError,
+                "The forkserver can't wait for register input breakpoints",
+            ));
+        }
+
+        // A server that failed is dropped, which stops it
+        let deadline = Instant::now() + timeout;
+        let mut server = self.take(path, env)?;
+        let pid = server.fork(input, deadline)?;
+        let attached = attach(pid)?;
+        let report = server.run(pid, deadline)?;
+        self.servers.lock().unwrap().push(server);
+
+        Ok((attached, report))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ForkPoint, ForkServerPool};
+    use crate::generators::Input;
+    use crate::process::FixedEnv;
+    use std::path::Path;
+    use std::time::Duration;
+
+    #[test]
+    fn fixed_env_applied() {
+        let env = FixedEnv {
+            vars: vec![("B7_FIXED".to_string(), "yes".to_string())],
+            ..FixedEnv::default()
+        };
+        let input = Input {
+            argv: Some(vec![
+                b"-c".to_vec(),
+                b"test \"$B7_FIXED\" = yes && test -z \"$HOME\"".to_vec(),
+            ]),
+            ..Input::default()
+        };
+        let pool = ForkServerPool::new(ForkPoint::Main);
+        let (_, report) = pool
+            .run(
+                Path::new("/bin/sh"),
+                Some(&env),
+                &input,
+                Duration::from_secs(5),
+                |_| Ok(()),
+            )
+            .unwrap();
+        assert_eq!(report.exit_status, 0);
+    }
+}
@@ -1,14 +1,40 @@
 use itertools::Itertools;
 use std::collections::HashMap;
 
+use crate::charset::Charset;
+use crate::conversation::Conversation;
 use crate::errors::Runner::ArgError;
 use crate::errors::SolverError;
 use crate::errors::SolverResult;
+use crate::socket::SocketTarget;
 use crate::IS_X86;
 
 type StringType = Vec<u8>;
 type ArgumentType = Vec<StringType>;
 
+/// Argument that is replaced with the path of an input file
+pub const FILE_PLACEHOLDER: &[u8] = b"@@";
+
+/// Parse a comma separated key-value list of the format:
+///
+/// ``` text
+/// key=value,key=value
+/// ```
+///
+/// into a `HashMap`, failing with `usage_error` if it is malformed
+pub(crate) fn parse_arg_opts<'a>(
+    arg: &'a str,
+    usage_error: &str,
+) -> SolverResult<HashMap<&'a str, &'a str>> {
+    arg.split(',')
+        .map(|opt| {
+            opt.split('=')
+                .collect_tuple::<(&str, &str)>()
+                .ok_or_else(|| SolverError::new(ArgError, usage_error))
+        })
+        .collect()
+}
+
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 /// Input to a memory buffer
 pub struct MemInput {
@@ -31,15 +57,7 @@ impl MemInput {
     /// ```
     pub fn parse_from_arg(arg: &str) -> SolverResult<Option<Self>> {
         debug!("Executing parse_from_arg:");
-        // Parse comma separated key-value list into a `HashMap`
-        let opts = arg
-            .split(',')
-            .map(|opt| {
-                opt.split('=')
-                    .collect_tuple::<(&str, &str)>()
-                    .ok_or_else(|| SolverError::new(ArgError, "Invalid memory input usage"))
-            })
-            .collect::<SolverResult<HashMap<&str, &str>>>()?;
+        let opts = parse_arg_opts(arg, "Invalid memory input usage")?;
 
         // Parse initial input to bytes
         let bytes = opts.get("init").unwrap_or(&"");
@@ -98,6 +116,120 @@ impl std::fmt::Display for MemInput {
     }
 }
 
+/// Registers a `RegInput` can set
+pub const REGISTERS: &[&str] = &[
+    "rax", "rbx", "rcx", "rdx", "rsi", "rdi", "rbp", "rsp", "r8", "r9", "r10", "r11", "r12", "r13",
+    "r14", "r15",
+];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Input to a register, set when a breakpoint is reached
+pub struct RegInput {
+    /// Name of the register, one of `REGISTERS`
+    pub reg: String,
+    /// Number of low bytes of the register to set
+    pub size: usize,
+    /// Bytes to load into the register, least significant first. Higher
+    /// bytes of the register are kept.
+    pub bytes: StringType,
+    /// Address to place breakpoint before `bytes` is loaded into the register
+    pub breakpoint: usize,
+}
+
+impl RegInput {
+    /// Parse a register input from an argument of the format:
+    ///
+    /// ``` text
+    /// bp=XXX,reg=rax,size=YYY,init=ZZZ
+    /// ```
+    ///
+    /// `init` holds the bytes of the value, least significant first.
+    pub fn parse_from_arg(arg: &str) -> SolverResult<Self> {
+        debug!("Executing parse_from_arg:");
+        let opts = parse_arg_opts(arg, "Invalid register input usage")?;
+
+        if !IS_X86 {
+            return Err(SolverError::new(ArgError, "Breakpoints only work on x86"));
+        }
+
+        // Parse breakpoint address to integer
+        let breakpoint = opts.get("bp");
+        let breakpoint = breakpoint
+            .ok_or_else(|| SolverError::new(ArgError, "Register input has no breakpoint"))?;
+        let breakpoint = usize::from_str_radix(breakpoint, 0x10);
+        let breakpoint = breakpoint
+            .map_err(|_| SolverError::new(ArgError, "Invalid register input breakpoint address"))?;
+
+        let reg = opts.get("reg");
+        let reg =
+            reg.ok_or_else(|| SolverError::new(ArgError, "Register input has no register"))?;
+        if !REGISTERS.contains(reg) {
+            return Err(SolverError::new(
+                ArgError,
+                "Unknown register input register",
+            ));
+        }
+
+        // Parse size to integer
+        let size = opts.get("size").unwrap_or(&"8");
+        let size = usize::from_str_radix(size, 0x10);
+        let size = size.map_err(|_| SolverError::new(ArgError, "Invalid register input size"))?;
+        if size == 0 || size > 8 {
+            return Err(SolverError::new(
+                ArgError,
+                "Register input size must be 1 to 8 bytes",
+            ));
+        }
+
+        // Parse initial input to bytes
+        let bytes = opts.get("init").unwrap_or(&"");
+        let bytes = hex::decode(bytes);
+        let bytes =
+            bytes.map_err(|_| SolverError::new(ArgError, "Invalid initial register input"))?;
+        if bytes.len() > size {
+            return Err(SolverError::new(
+                ArgError,
+                "Initial register input is larger than its size",
+            ));
+        }
+
+        Ok(Self {
+            reg: reg.to_string(),
+            size,
+            bytes,
+            breakpoint,
+        })
+    }
+
+    /// Set the low bytes of `value` to `bytes`
+    pub fn apply(&self, value: u64) -> u64 {
+        let mask = match self.bytes.len() {
+            8 => std::u64::MAX,
+            len => (1 << (len * 8)) - 1,
+        };
+        let bytes = self
+            .bytes
+            .iter()
+            .rev()
+            .fold(0, |acc, &byte| acc << 8 | u64::from(byte));
+        value & !mask | bytes
+    }
+}
+
+impl std::fmt::Display for RegInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        debug!("Executing fmt:");
+        write!(
+            f,
+            "{}[{}]@{:#x} = {}",
+            self.reg,
+            self.size,
+            self.breakpoint,
+            hex::encode(&self.bytes)
+        )
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 /// Holds the various input the runner is expected to use
 pub struct Input {
@@ -107,6 +239,16 @@ pub struct Input {
     pub stdinlen: Option<u32>,
     pub stdin: Option<StringType>,
     pub mem: Option<Vec<MemInput>>,
+    /// Registers to set at breakpoints
+    pub reg: Option<Vec<RegInput>>,
+    /// Environment variables as name and value pairs
+    pub env: Option<Vec<(StringType, StringType)>>,
+    /// Contents of the files whose paths replace the `@@` arguments, in order
+    pub files: Option<Vec<StringType>>,
+    /// Where to send `stdin` to instead of the standard input of the target
+    pub socket: Option<SocketTarget>,
+    /// Conversation to send `stdin` in, at its input step
+    pub conversation: Option<Conversation>,
 }
 
 impl Input {
@@ -142,9 +284,59 @@ impl Input {
         if tmp.mem.is_some() {
             res.mem = tmp.mem;
         }
+        if tmp.files.is_some() {
+            res.files = tmp.files;
+        }
+        if tmp.socket.is_some() {
+            res.socket = tmp.socket;
+        }
+        if tmp.conversation.is_some() {
+            res.conversation = tmp.conversation;
+        }
+        // Registers are replaced one by one, so others that were solved stay
+        if let Some(tmp_reg) = tmp.reg {
+            let regs = res.reg.get_or_insert_with(Vec::new);
+            for reg in tmp_reg {
+                match regs
+                    .iter_mut()
+                    .find(|r| r.reg == reg.reg && r.breakpoint == reg.breakpoint)
+                {
+                    Some(r) => *r = reg,
+                    None => regs.push(reg),
+                }
+            }
+        }
+        // Variables are replaced one by one, so others that were solved stay
+        if let Some(tmp_env) = tmp.env {
+            let env = res.env.get_or_insert_with(Vec::new);
+            for (name, value) in tmp_env {
+                match env.iter_mut().find(|(var, _)| *var == name) {
+                    Some(var) => var.1 = value,
+                    None => env.push((name, value)),
+                }
+            }
+        }
 
         res
     }
+
+    /// Number of input files, one per `@@` argument
+    pub fn file_count(&self) -> usize {
+        self.argv.as_ref().map_or(0, |argv| {
+            argv.iter()
+                .filter(|arg| arg.as_slice() == FILE_PLACEHOLDER)
+                .count()
+        })
+    }
+
+    /// Get the value of the environment variable `name`
+    pub fn env_var(&self, name: &[u8]) -> Option<&StringType> {
+        self.env
+            .as_ref()?
+            .iter()
+            .find(|(var, _)| var.as_slice() == name)
+            .map(|(_, value)| value)
+    }
 }
 
 pub type GenItem = u32;
@@ -157,6 +349,152 @@ pub trait Update: Iterator {
     ///
     /// * `chosen` - the value that was found to be correct
     fn update(&mut self, chosen: GenItem) -> bool;
+
+    /// signals to the generator that no input of the round stood out. Returns
+    /// whether the generator changed the inputs it tries, so the round
+    /// should be run again instead of updating with the best guess.
+    fn no_signal(&mut self) -> bool {
+        false
+    }
+}
+
+/// Most bytes a generator brute forces per round, since they are packed
+/// into one `GenItem`
+pub const MAX_CHUNK: usize = 4;
+
+/// Most runs a round widened to two bytes may take, so only charsets of up
+/// to 64 bytes are widened
+const MAX_WIDENED_RUNS: usize = 4096;
+
+/// Pack the bytes brute forced in one round into a `GenItem`, first byte
+/// lowest
+fn pack(bytes: &[u8]) -> GenItem {
+    bytes
+        .iter()
+        .rev()
+        .fold(0, |acc, &byte| acc << 8 | GenItem::from(byte))
+}
+
+/// Unpack `len` bytes packed by `pack`
+fn unpack(item: GenItem, len: usize) -> StringType {
+    (0..len).map(|i| (item >> (8 * i)) as u8).collect()
+}
+
+/// Every combination of `len` bytes of a charset, the candidates of a round
+/// that brute forces `len` consecutive bytes
+#[derive(Debug, Clone)]
+struct Combinations {
+    charset: Charset,
+    /// Charset index of each byte of the next combination, `None` once all
+    /// combinations were made
+    next: Option<Vec<usize>>,
+}
+
+impl Combinations {
+    fn new(charset: &Charset, len: usize) -> Combinations {
+        let next = if charset.is_empty() || len == 0 {
+            None
+        } else {
+            Some(vec![0; len])
+        };
+        Combinations {
+            charset: charset.clone(),
+            next,
+        }
+    }
+}
+
+impl Iterator for Combinations {
+    type Item = StringType;
+
+    fn next(&mut self) -> Option<StringType> {
+        let mut idxs = self.next.take()?;
+        let bytes = idxs.iter().map(|&i| self.charset.chars()[i]).collect();
+
+        // The last byte changes fastest, carrying into the ones before it
+        for pos in (0..idxs.len()).rev() {
+            idxs[pos] += 1;
+            if idxs[pos] < self.charset.len() {
+                self.next = Some(idxs);
+                break;
+            }
+            idxs[pos] = 0;
+        }
+
+        Some(bytes)
+    }
+}
+
+/// The rounds of a generator that brute forces a run of bytes a chunk at a
+/// time. Each round tries every combination of the charset for the next
+/// chunk of bytes.
+#[derive(Debug, Clone)]
+struct Chunks {
+    charset: Charset,
+    /// Bytes brute forced per round
+    chunk: usize,
+    /// Larger chunk used while single bytes show no signal
+    widened: Option<usize>,
+    /// Bytes brute forced in the current round
+    len: usize,
+    /// Candidates left in the current round
+    round: Combinations,
+}
+
+impl Chunks {
+    /// Rounds of one byte of `charset`, with `left` bytes to brute force
+    fn new(charset: Charset, left: usize) -> Chunks {
+        let mut chunks = Chunks {
+            round: Combinations::new(&charset, 0),
+            charset,
+            chunk: 1,
+            widened: None,
+            len: 0,
+        };
+        chunks.start(left);
+        chunks
+    }
+
+    /// Brute force `chunk` bytes per round, at most `MAX_CHUNK`
+    fn set_chunk(&mut self, chunk: usize, left: usize) {
+        self.chunk = chunk.max(1).min(MAX_CHUNK);
+        self.start(left);
+    }
+
+    /// Make the candidates of the next round, with `left` bytes to brute
+    /// force
+    fn start(&mut self, left: usize) {
+        self.len = self.widened.unwrap_or(self.chunk).min(left);
+        self.round = Combinations::new(&self.charset, self.len);
+    }
+
+    /// Bytes of the candidate `chosen` of the current round. A widened
+    /// round is over once it is chosen from, so the following rounds use
+    /// the configured chunk again.
+    fn chosen(&mut self, chosen: GenItem) -> StringType {
+        self.widened = None;
+        unpack(chosen, self.len)
+    }
+
+    /// Brute force two bytes per round until a round is chosen from, if
+    /// rounds brute force single bytes, `left` has two and a round stays
+    /// within `MAX_WIDENED_RUNS`. Returns whether the rounds changed.
+    fn widen(&mut self, left: usize) -> bool {
+        if self.len > 1 || left < 2 || self.charset.len().pow(2) > MAX_WIDENED_RUNS {
+            return false;
+        }
+        self.widened = Some(2);
+        self.start(left);
+        true
+    }
+}
+
+impl Iterator for Chunks {
+    type Item = StringType;
+
+    fn next(&mut self) -> Option<StringType> {
+        self.round.next()
+    }
 }
 
 // Generate trait: has iteration and updating with right Id type
@@ -181,7 +519,7 @@ pub trait Events {
 impl<T: Iterator<Item = (GenItem, Input)> + Update> Generate for T {}
 
 /* code for stdin generators */
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct StdinLenGenerator {
     len: GenItem,
     max: GenItem,
@@ -242,17 +580,16 @@ impl Update for StdinLenGenerator {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct StdinCharGenerator {
     padlen: Option<u32>,
     padchr: u8,
     prefix: StringType,
     suffix: StringType,
     idx: u32,
-    cur: u16,
+    /// Rounds brute forcing the next bytes
+    chunks: Chunks,
     correct: StringType,
-    min: u16,
-    max: u16,
 }
 
 // allowing printing of string in flag
@@ -263,33 +600,45 @@ impl std::fmt::Display for StdinCharGenerator {
 }
 
 impl StdinCharGenerator {
-    pub fn new(input: Input, min: u16, max: u16) -> StdinCharGenerator {
-        StdinCharGenerator {
+    pub fn new(input: Input, charset: Charset) -> StdinCharGenerator {
+        let mut gen = StdinCharGenerator {
             padlen: input.stdinlen,
             padchr: 0x41,
             prefix: vec![],
             suffix: vec![],
             idx: 0,
-            cur: min,
+            chunks: Chunks::new(charset, 0),
             correct: vec![],
-            min,
-            max,
-        }
+        };
+        gen.set_chunk(1);
+        gen
     }
 
-    pub fn new_start(input: Input, min: u16, max: u16, start: &[u8]) -> StdinCharGenerator {
+    pub fn new_start(input: Input, charset: Charset, start: &[u8]) -> StdinCharGenerator {
         warn!("{:?}", start);
-        StdinCharGenerator {
+        let mut gen = StdinCharGenerator {
             padlen: input.stdinlen,
             padchr: 0x41,
             prefix: start.to_vec(),
             suffix: vec![],
             idx: start.len() as u32,
-            cur: min,
+            chunks: Chunks::new(charset, 0),
             correct: vec![],
-            min,
-            max,
-        }
+        };
+        gen.set_chunk(1);
+        gen
+    }
+
+    /// Brute force `chunk` bytes per round, at most `MAX_CHUNK`. Rounds try
+    /// every combination of the charset, so keep the charset small.
+    pub fn set_chunk(&mut self, chunk: usize) {
+        let left = self.remaining();
+        self.chunks.set_chunk(chunk, left);
+    }
+
+    /// Bytes left to brute force
+    fn remaining(&self) -> usize {
+        self.padlen.unwrap_or(0).saturating_sub(self.idx) as usize
     }
 
     // Decide which character to use for padding
@@ -315,15 +664,14 @@ impl Iterator for StdinCharGenerator {
     fn next(&mut self) -> Option<Self::Item> {
         // check if we have anymore to solve
         let padlen = self.padlen?;
-        if self.idx >= padlen || self.cur > 255 || self.cur > self.max {
+        if self.idx >= padlen {
             return None;
         }
-        let chr = self.cur as u8;
-        self.cur += 1;
+        let chunk = self.chunks.next()?;
         let mut inp: StringType = Vec::new();
         inp.extend_from_slice(&self.prefix);
         inp.extend_from_slice(&self.correct);
-        inp.push(chr);
+        inp.extend_from_slice(&chunk);
         inp.extend_from_slice(&self.suffix);
         // add padding to reach the required length
         while inp.len() > padlen as usize {
@@ -334,7 +682,7 @@ impl Iterator for StdinCharGenerator {
         }
         let mut res = Input::new();
         res.stdin = Some(inp);
-        Some((chr as GenItem, res))
+        Some((pack(&chunk), res))
     }
 }
 
@@ -348,19 +696,320 @@ impl Events for StdinCharGenerator {
 // update hook for stdin
 impl Update for StdinCharGenerator {
     fn update(&mut self, chosen: GenItem) -> bool {
-        self.correct.push(chosen as u8);
-        self.idx += 1;
-        self.cur = self.min as u16;
+        let bytes = self.chunks.chosen(chosen);
+        self.idx += bytes.len() as u32;
+        self.correct.extend(bytes);
+        let left = self.remaining();
+        self.chunks.start(left);
         self.on_update();
         if let Some(i) = self.padlen {
             return self.idx < i;
         }
         false
     }
+
+    /// Checks on several bytes at once only show a signal when all of them
+    /// are right, so try two bytes in the next round
+    fn no_signal(&mut self) -> bool {
+        let left = self.remaining();
+        if !self.chunks.widen(left) {
+            return false;
+        }
+        warn!(
+            "No input stood out, brute forcing 2 bytes this round ({} runs)",
+            self.chunks.charset.len().pow(2)
+        );
+        true
+    }
+}
+
+/* code for environment variable generators */
+#[derive(Debug, Clone)]
+pub struct EnvLenGenerator {
+    name: StringType,
+    len: GenItem,
+    max: GenItem,
+    correct: GenItem,
+}
+
+impl std::fmt::Display for EnvLenGenerator {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.correct)
+    }
+}
+
+impl EnvLenGenerator {
+    /// Find the length of the value of the variable `name`
+    pub fn new(name: &[u8], min: GenItem, max: GenItem) -> EnvLenGenerator {
+        EnvLenGenerator {
+            name: name.to_vec(),
+            len: min,
+            max,
+            correct: 0,
+        }
+    }
+
+    pub fn get_length(&self) -> GenItem {
+        self.correct
+    }
+}
+
+impl Iterator for EnvLenGenerator {
+    type Item = (GenItem, Input);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len > self.max {
+            return None;
+        }
+        let sz = self.len;
+        self.len += 1;
+        let mut res = Input::new();
+        res.env = Some(vec![(self.name.clone(), vec![0x41; sz as usize])]);
+        Some((sz, res))
+    }
+}
+
+impl Events for EnvLenGenerator {
+    fn on_update(&self) {
+        info!(
+            "{} length: {}",
+            String::from_utf8_lossy(&self.name),
+            self.correct
+        );
+    }
+}
+
+impl Update for EnvLenGenerator {
+    fn update(&mut self, chosen: GenItem) -> bool {
+        self.correct = chosen;
+        self.on_update();
+        false
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EnvCharGenerator {
+    name: StringType,
+    len: usize,
+    padchr: u8,
+    /// Rounds brute forcing the next bytes
+    chunks: Chunks,
+    correct: StringType,
+}
+
+impl std::fmt::Display for EnvCharGenerator {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(self.correct.as_slice()))
+    }
+}
+
+impl EnvCharGenerator {
+    /// Find the value of the variable `name`, as long as its value in `input`
+    pub fn new(input: &Input, name: &[u8], charset: Charset) -> EnvCharGenerator {
+        let len = input.env_var(name).map_or(0, Vec::len);
+        EnvCharGenerator {
+            name: name.to_vec(),
+            len,
+            padchr: 0x41,
+            chunks: Chunks::new(charset, len),
+            correct: vec![],
+        }
+    }
+
+    /// Brute force `chunk` bytes per round, at most `MAX_CHUNK`
+    pub fn set_chunk(&mut self, chunk: usize) {
+        self.chunks.set_chunk(chunk, self.len - self.correct.len());
+    }
+
+    pub fn get_value(&self) -> &StringType {
+        &self.correct
+    }
+}
+
+impl Iterator for EnvCharGenerator {
+    type Item = (GenItem, Input);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.correct.len() >= self.len {
+            return None;
+        }
+        let chunk = self.chunks.next()?;
+        let mut value = self.correct.clone();
+        value.extend_from_slice(&chunk);
+        value.resize(self.len, self.padchr);
+        let mut res = Input::new();
+        res.env = Some(vec![(self.name.clone(), value)]);
+        Some((pack(&chunk), res))
+    }
+}
+
+impl Events for EnvCharGenerator {
+    fn on_update(&self) {
+        info!("{}={}", String::from_utf8_lossy(&self.name), self);
+    }
+}
+
+impl Update for EnvCharGenerator {
+    fn update(&mut self, chosen: GenItem) -> bool {
+        let bytes = self.chunks.chosen(chosen);
+        self.correct.extend(bytes);
+        self.chunks.start(self.len - self.correct.len());
+        self.on_update();
+        self.correct.len() < self.len
+    }
+}
+
+/* code for file generators */
+/// Input files with file `idx` replaced by `contents`
+fn with_file(files: &[StringType], idx: usize, contents: StringType) -> Input {
+    let mut files = files.to_vec();
+    if files.len() <= idx {
+        files.resize(idx + 1, vec![]);
+    }
+    files[idx] = contents;
+
+    let mut res = Input::new();
+    res.files = Some(files);
+    res
+}
+
+#[derive(Debug, Clone)]
+pub struct FileLenGenerator {
+    idx: usize,
+    files: Vec<StringType>,
+    len: GenItem,
+    max: GenItem,
+    correct: GenItem,
+}
+
+impl std::fmt::Display for FileLenGenerator {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.correct)
+    }
+}
+
+impl FileLenGenerator {
+    /// Find the length of input file `idx`, keeping the other files of `input`
+    pub fn new(input: &Input, idx: usize, min: GenItem, max: GenItem) -> FileLenGenerator {
+        FileLenGenerator {
+            idx,
+            files: input.files.clone().unwrap_or_default(),
+            len: min,
+            max,
+            correct: 0,
+        }
+    }
+
+    pub fn get_length(&self) -> GenItem {
+        self.correct
+    }
+}
+
+impl Iterator for FileLenGenerator {
+    type Item = (GenItem, Input);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len > self.max {
+            return None;
+        }
+        let sz = self.len;
+        self.len += 1;
+        let res = with_file(&self.files, self.idx, vec![0x41; sz as usize]);
+        Some((sz, res))
+    }
+}
+
+impl Events for FileLenGenerator {
+    fn on_update(&self) {
+        info!("file {} length: {}", self.idx, self.correct);
+    }
+}
+
+impl Update for FileLenGenerator {
+    fn update(&mut self, chosen: GenItem) -> bool {
+        self.correct = chosen;
+        self.on_update();
+        false
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FileCharGenerator {
+    idx: usize,
+    files: Vec<StringType>,
+    len: usize,
+    padchr: u8,
+    /// Rounds brute forcing the next bytes
+    chunks: Chunks,
+    correct: StringType,
+}
+
+impl std::fmt::Display for FileCharGenerator {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(self.correct.as_slice()))
+    }
+}
+
+impl FileCharGenerator {
+    /// Find the contents of input file `idx`, as long as the file is in
+    /// `input`
+    pub fn new(input: &Input, idx: usize, charset: Charset) -> FileCharGenerator {
+        let files = input.files.clone().unwrap_or_default();
+        let len = files.get(idx).map_or(0, Vec::len);
+        FileCharGenerator {
+            idx,
+            len,
+            files,
+            padchr: 0x41,
+            chunks: Chunks::new(charset, len),
+            correct: vec![],
+        }
+    }
+
+    /// Brute force `chunk` bytes per round, at most `MAX_CHUNK`
+    pub fn set_chunk(&mut self, chunk: usize) {
+        self.chunks.set_chunk(chunk, self.len - self.correct.len());
+    }
+
+    pub fn get_contents(&self) -> &StringType {
+        &self.correct
+    }
+}
+
+impl Iterator for FileCharGenerator {
+    type Item = (GenItem, Input);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.correct.len() >= self.len {
+            return None;
+        }
+        let chunk = self.chunks.next()?;
+        let mut contents = self.correct.clone();
+        contents.extend_from_slice(&chunk);
+        contents.resize(self.len, self.padchr);
+        let res = with_file(&self.files, self.idx, contents);
+        Some((pack(&chunk), res))
+    }
+}
+
+impl Events for FileCharGenerator {
+    fn on_update(&self) {
+        info!("file {}: {}", self.idx, self);
+    }
+}
+
+impl Update for FileCharGenerator {
+    fn update(&mut self, chosen: GenItem) -> bool {
+        let bytes = self.chunks.chosen(chosen);
+        self.correct.extend(bytes);
+        self.chunks.start(self.len - self.correct.len());
+        self.on_update();
+        self.correct.len() < self.len
+    }
 }
 
 /* code for argv generators */
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ArgcGenerator {
     len: GenItem,
     max: GenItem,
@@ -422,7 +1071,7 @@ impl Update for ArgcGenerator {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ArgvLenGenerator {
     len: u32,
     min: u32,
@@ -503,18 +1152,17 @@ impl Update for ArgvLenGenerator {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ArgvGenerator {
     len: Vec<u32>,
     padchr: u8,
     idx: u32,
-    min: u16,
-    max: u16,
+    charset: Charset,
     pos: usize,
     argc: u32,
     correct: ArgumentType,
     current: StringType,
-    cur: u16,
+    cur: usize,
 }
 
 // properly format Argv string
@@ -529,18 +1177,17 @@ impl std::fmt::Display for ArgvGenerator {
 
 // argv constructor
 impl ArgvGenerator {
-    pub fn new(argc: u32, len: &[u32], min: u16, max: u16) -> ArgvGenerator {
+    pub fn new(argc: u32, len: &[u32], charset: Charset) -> ArgvGenerator {
         ArgvGenerator {
             len: len.to_vec(),
             padchr: 0x41,
             idx: 0,
-            min,
-            max,
+            charset,
             pos: 0,
             argc,
             correct: vec![vec![]; argc as usize],
             current: vec![],
-            cur: min,
+            cur: 0,
         }
     }
 
@@ -564,10 +1211,10 @@ impl Iterator for ArgvGenerator {
             }
         }
         let len: u32 = self.len[self.pos];
-        if self.idx >= len || self.cur > 255 || self.cur > self.max {
+        if self.idx >= len {
             return None;
         }
-        let chr = self.cur as u8;
+        let chr = self.charset.get(self.cur)?;
         self.cur += 1;
         //generate current string
         let mut argv: ArgumentType = Vec::new();
@@ -616,7 +1263,7 @@ impl Update for ArgvGenerator {
         // push new guess to state
         self.correct[self.pos].push(chosen as u8);
         self.current.push(chosen as u8);
-        self.cur = self.min as u16;
+        self.cur = 0;
         self.idx += 1;
         self.on_update();
 
@@ -629,24 +1276,36 @@ impl Update for ArgvGenerator {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 /// Generator for brute forcing inputs to a memory region
 pub struct MemGenerator {
-    /// Current byte being tested
-    cur: u16,
+    /// Rounds brute forcing the next bytes
+    chunks: Chunks,
     /// Correct portion of the input
     correct: MemInput,
 }
 
 impl MemGenerator {
-    /// Make `MemGenerator` from a `MemInput`
-    pub fn new(mem_input: MemInput) -> Self {
+    /// Make `MemGenerator` from a `MemInput`, testing the bytes of `charset`
+    pub fn new(mem_input: MemInput, charset: Charset) -> Self {
+        let left = mem_input.size.saturating_sub(mem_input.bytes.len());
         Self {
-            cur: 0,
+            chunks: Chunks::new(charset, left),
             correct: mem_input,
         }
     }
 
+    /// Brute force `chunk` bytes per round, at most `MAX_CHUNK`
+    pub fn set_chunk(&mut self, chunk: usize) {
+        let left = self.left();
+        self.chunks.set_chunk(chunk, left);
+    }
+
+    /// Bytes left to brute force
+    fn left(&self) -> usize {
+        self.correct.size.saturating_sub(self.correct.bytes.len())
+    }
+
     /// Get correct portion of input so far
     pub fn get_mem_input(self) -> MemInput {
         self.correct
@@ -662,15 +1321,14 @@ impl Iterator for MemGenerator {
     type Item = (GenItem, Input);
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.cur > 255 || self.finished() {
+        if self.finished() {
             return None;
         }
 
-        let cur = self.cur as u8;
-        self.cur += 1;
+        let chunk = self.chunks.next()?;
 
         let mut try_bytes = self.correct.bytes.clone();
-        try_bytes.push(cur);
+        try_bytes.extend_from_slice(&chunk);
 
         let mem = MemInput {
             size: self.correct.size,
@@ -686,15 +1344,17 @@ impl Iterator for MemGenerator {
             ..Default::default()
         };
 
-        Some((cur as GenItem, input))
+        Some((pack(&chunk), input))
     }
 }
 
 impl Update for MemGenerator {
-    /// Tell generator which byte was correct
+    /// Tell generator which bytes were correct
     fn update(&mut self, chosen: GenItem) -> bool {
-        self.correct.bytes.push(chosen as u8);
-        self.cur = 0;
+        let bytes = self.chunks.chosen(chosen);
+        self.correct.bytes.extend(bytes);
+        let left = self.left();
+        self.chunks.start(left);
         self.on_update();
         !self.finished()
     }
@@ -711,3 +1371,595 @@ impl Events for MemGenerator {
         info!("mem: {}", self);
     }
 }
+
+#[derive(Debug, Clone)]
+/// Generator for brute forcing a register input byte by byte, least
+/// significant byte first
+pub struct RegGenerator {
+    /// Rounds brute forcing the next bytes
+    chunks: Chunks,
+    /// Correct portion of the input
+    correct: RegInput,
+}
+
+impl RegGenerator {
+    /// Make `RegGenerator` from a `RegInput`, testing the bytes of `charset`
+    pub fn new(reg_input: RegInput, charset: Charset) -> Self {
+        let left = reg_input.size.saturating_sub(reg_input.bytes.len());
+        Self {
+            chunks: Chunks::new(charset, left),
+            correct: reg_input,
+        }
+    }
+
+    /// Brute force `chunk` bytes per round, at most `MAX_CHUNK`
+    pub fn set_chunk(&mut self, chunk: usize) {
+        let left = self.left();
+        self.chunks.set_chunk(chunk, left);
+    }
+
+    /// Bytes left to brute force
+    fn left(&self) -> usize {
+        self.correct.size.saturating_sub(self.correct.bytes.len())
+    }
+
+    /// Get correct portion of input so far
+    pub fn get_reg_input(self) -> RegInput {
+        self.correct
+    }
+
+    /// Is brute forcing done?
+    pub fn finished(&self) -> bool {
+        self.correct.bytes.len() == self.correct.size
+    }
+}
+
+impl Iterator for RegGenerator {
+    type Item = (GenItem, Input);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished() {
+            return None;
+        }
+
+        let chunk = self.chunks.next()?;
+
+        let mut reg = self.correct.clone();
+        reg.bytes.extend_from_slice(&chunk);
+
+        let input = Input {
+            reg: Some(vec![reg]),
+            ..Default::default()
+        };
+
+        Some((pack(&chunk), input))
+    }
+}
+
+impl Update for RegGenerator {
+    /// Tell generator which bytes were correct
+    fn update(&mut self, chosen: GenItem) -> bool {
+        let bytes = self.chunks.chosen(chosen);
+        self.correct.bytes.extend(bytes);
+        let left = self.left();
+        self.chunks.start(left);
+        self.on_update();
+        !self.finished()
+    }
+}
+
+impl std::fmt::Display for RegGenerator {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.correct)
+    }
+}
+
+impl Events for RegGenerator {
+    fn on_update(&self) {
+        info!("reg: {}", self);
+    }
+}
+
+/* code for numeric generators */
+/// Where a `NumberGenerator` places the number it searches
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NumberDest {
+    /// stdin, followed by a newline
+    Stdin,
+    /// The argument at this index of `Input::argv`
+    Arg(usize),
+    /// A memory buffer, followed by a NUL byte if it fits
+    Mem(MemInput),
+}
+
+/// How a `NumberGenerator` searches the number
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberSearch {
+    /// Sign, then number of digits, then each digit. Finds numbers that are
+    /// checked digit by digit or compared as strings.
+    Digits,
+    /// Halve the range with every round. Finds numbers that are compared
+    /// with `<` and `>`.
+    Binary,
+}
+
+/// A number to search with a `NumberGenerator`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NumberInput {
+    /// Where to place the number
+    pub dest: NumberDest,
+    /// Radix the number is written in, 10 or 16
+    pub radix: u32,
+    /// How to search the number
+    pub search: NumberSearch,
+    /// Smallest number to try. Numbers are only signed if this is negative.
+    pub min: i64,
+    /// Largest number to try
+    pub max: i64,
+}
+
+impl Default for NumberInput {
+    /// Signed 32 bit decimal on stdin, searched digit by digit
+    fn default() -> Self {
+        NumberInput {
+            dest: NumberDest::Stdin,
+            radix: 10,
+            search: NumberSearch::Digits,
+            min: i64::from(std::i32::MIN),
+            max: i64::from(std::i32::MAX),
+        }
+    }
+}
+
+impl NumberInput {
+    /// Parse a numeric input from an argument of the format:
+    ///
+    /// ``` text
+    /// in=stdin|argvN|mem,base=10|16,search=digits|binary,min=XXX,max=YYY
+    /// ```
+    ///
+    /// Every option is optional. `min` and `max` are decimal. `argvN` is
+    /// `argv[N]`, starting at 1. With `in=mem` the buffer is given by the
+    /// `addr`, `size` and `breakpoint` options of `--mem-brute`.
+    pub fn parse_from_arg(arg: &str) -> SolverResult<Self> {
+        debug!("Executing parse_from_arg:");
+        let opts = parse_arg_opts(arg, "Invalid numeric input usage")?;
+        let mut number = NumberInput::default();
+
+        number.dest = match opts.get("in") {
+            None | Some(&"stdin") => NumberDest::Stdin,
+            Some(&"mem") => match MemInput::parse_from_arg(arg)? {
+                Some(mem) => NumberDest::Mem(MemInput {
+                    bytes: vec![],
+                    ..mem
+                }),
+                None => return Err(SolverError::new(ArgError, "Invalid numeric memory input")),
+            },
+            Some(dest) if dest.starts_with("argv") => match dest[4..].parse::<usize>() {
+                Ok(n) if n > 0 => NumberDest::Arg(n - 1),
+                _ => return Err(SolverError::new(ArgError, "Invalid numeric argv index")),
+            },
+            Some(_) => return Err(SolverError::new(ArgError, "Invalid numeric input target")),
+        };
+
+        number.radix = match opts.get("base") {
+            None | Some(&"10") => 10,
+            Some(&"16") => 16,
+            Some(_) => return Err(SolverError::new(ArgError, "Numeric base must be 10 or 16")),
+        };
+
+        number.search = match opts.get("search") {
+            None | Some(&"digits") => NumberSearch::Digits,
+            Some(&"binary") => NumberSearch::Binary,
+            Some(_) => {
+                return Err(SolverError::new(
+                    ArgError,
+                    "Numeric search must be digits or binary",
+                ))
+            }
+        };
+
+        let bound = |name: &str, default: i64| match opts.get(name) {
+            Some(bound) => bound
+                .parse()
+                .map_err(|_| SolverError::new(ArgError, "Invalid numeric input bound")),
+            None => Ok(default),
+        };
+        number.min = bound("min", number.min)?;
+        number.max = bound("max", number.max)?;
+        if number.min > number.max {
+            return Err(SolverError::new(
+                ArgError,
+                "Numeric input min is larger than max",
+            ));
+        }
+
+        Ok(number)
+    }
+
+    /// Write `value` in the radix of the number
+    fn format(&self, value: i64) -> StringType {
+        let sign = if value < 0 { "-" } else { "" };
+        let abs = i128::from(value).abs();
+        let text = match self.radix {
+            16 => format!("{}{:x}", sign, abs),
+            _ => format!("{}{}", sign, abs),
+        };
+        text.into_bytes()
+    }
+
+    /// Most digits a number between `min` and `max` has
+    fn max_digits(&self) -> usize {
+        let largest = std::cmp::max(i128::from(self.min).abs(), i128::from(self.max).abs());
+        let mut digits = 1;
+        let mut left = largest / i128::from(self.radix);
+        while left > 0 {
+            digits += 1;
+            left /= i128::from(self.radix);
+        }
+        digits
+    }
+}
+
+/// Which part of the number a `NumberGenerator` searches in this round
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NumberRound {
+    /// Whether the number is negative
+    Sign,
+    /// How many digits the number has
+    Len,
+    /// The next digit
+    Digit,
+    /// Which half of the range the number is in
+    Range,
+    /// Which of the last few numbers of the range it is
+    Last,
+}
+
+/// Ranges with at most this many numbers are tried number by number
+const NUMBER_LAST_ROUND: i64 = 3;
+
+#[derive(Debug, Clone)]
+/// Generator for integers that targets parse with `scanf`, `atoi` or
+/// `strtol`
+pub struct NumberGenerator {
+    number: NumberInput,
+    /// Argv to place an argument number in
+    argv: ArgumentType,
+    round: NumberRound,
+    /// Candidates of the current round
+    candidates: Vec<(GenItem, StringType)>,
+    /// Index of the next candidate
+    cur: usize,
+    /// Whether the number is negative
+    negative: bool,
+    /// Number of digits
+    len: usize,
+    /// Correct digits so far
+    digits: StringType,
+    /// Smallest number the binary search can still find
+    lo: i64,
+    /// Largest number the binary search can still find
+    hi: i64,
+}
+
+impl NumberGenerator {
+    /// Make a `NumberGenerator` searching `number`, with the other arguments
+    /// taken from `input`
+    pub fn new(input: &Input, number: NumberInput) -> Self {
+        let mut argv = input.argv.clone().unwrap_or_default();
+        if let NumberDest::Arg(idx) = number.dest {
+            if argv.len() <= idx {
+                argv.resize(idx + 1, vec![]);
+            }
+        }
+
+        let round = match number.search {
+            NumberSearch::Digits if number.min < 0 => NumberRound::Sign,
+            NumberSearch::Digits => NumberRound::Len,
+            NumberSearch::Binary if number.max - number.min < NUMBER_LAST_ROUND => {
+                NumberRound::Last
+            }
+            NumberSearch::Binary => NumberRound::Range,
+        };
+        let mut gen = NumberGenerator {
+            lo: number.min,
+            hi: number.max,
+            number,
+            argv,
+            round,
+            candidates: vec![],
+            cur: 0,
+            negative: false,
+            len: 0,
+            digits: vec![],
+        };
+        gen.candidates = gen.candidates();
+        gen
+    }
+
+    /// The digit characters of the radix
+    fn digit_chars(&self) -> &'static [u8] {
+        &b"0123456789abcdef"[..self.number.radix as usize]
+    }
+
+    /// Sign and the correct digits so far, followed by `next` and padded
+    /// with `pad` to the number of digits
+    fn digits_with(&self, next: &[u8], pad: u8) -> StringType {
+        let mut text = if self.negative { vec![b'-'] } else { vec![] };
+        text.extend_from_slice(&self.digits);
+        text.extend_from_slice(next);
+        while text.len() < self.len + self.negative as usize {
+            text.push(pad);
+        }
+        text
+    }
+
+    /// Middle of the remaining range
+    fn mid(&self) -> i64 {
+        ((i128::from(self.lo) + i128::from(self.hi)) / 2) as i64
+    }
+
+    /// Texts to try in the current round
+    fn candidates(&self) -> Vec<(GenItem, StringType)> {
+        match self.round {
+            NumberRound::Sign => vec![(0, b"1".to_vec()), (1, b"-1".to_vec())],
+            NumberRound::Len => (1..=self.number.max_digits())
+                .map(|len| (len as GenItem, self.digits_with(&vec![b'1'; len], b'0')))
+                .collect(),
+            NumberRound::Digit => self
+                .digit_chars()
+                .iter()
+                .map(|&digit| (digit as GenItem, self.digits_with(&[digit], b'0')))
+                .collect(),
+            NumberRound::Range => vec![
+                (0, self.number.format(self.lo)),
+                (1, self.number.format(self.mid())),
+                (2, self.number.format(self.hi)),
+            ],
+            NumberRound::Last => (0..=self.hi - self.lo)
+                .map(|offset| (offset as GenItem, self.number.format(self.lo + offset)))
+                .collect(),
+        }
+    }
+
+    /// `Input` with `text` placed where the number goes
+    fn input(&self, mut text: StringType) -> Input {
+        let mut input = Input::new();
+        match &self.number.dest {
+            NumberDest::Stdin => {
+                text.push(b'\n');
+                input.stdinlen = Some(text.len() as u32);
+                input.stdin = Some(text);
+            }
+            NumberDest::Arg(idx) => {
+                let mut argv = self.argv.clone();
+                argv[*idx] = text;
+                input.argv = Some(argv);
+            }
+            NumberDest::Mem(mem) => {
+                text.push(0);
+                text.truncate(mem.size);
+                input.mem = Some(vec![MemInput {
+                    bytes: text,
+                    ..mem.clone()
+                }]);
+            }
+        }
+        input
+    }
+}
+
+impl Iterator for NumberGenerator {
+    type Item = (GenItem, Input);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (item, text) = self.candidates.get(self.cur)?.clone();
+        self.cur += 1;
+        Some((item, self.input(text)))
+    }
+}
+
+impl Update for NumberGenerator {
+    /// Tell generator which candidate was correct
+    fn update(&mut self, chosen: GenItem) -> bool {
+        let more = match self.round {
+            NumberRound::Sign => {
+                self.negative = chosen == 1;
+                self.round = NumberRound::Len;
+                true
+            }
+            NumberRound::Len => {
+                self.len = chosen as usize;
+                self.round = NumberRound::Digit;
+                true
+            }
+            NumberRound::Digit => {
+                self.digits.push(chosen as u8);
+                self.digits.len() < self.len
+            }
+            NumberRound::Range => {
+                // The number is kept inside the range even if the middle was
+                // picked, since it differs from both ends when it is correct
+                let mid = self.mid();
+                match chosen {
+                    0 => self.hi = mid,
+                    2 => self.lo = mid,
+                    _ => {
+                        self.lo = mid;
+                        self.hi = mid;
+                    }
+                }
+                if self.hi - self.lo < NUMBER_LAST_ROUND {
+                    self.round = NumberRound::Last;
+                }
+                chosen != 1
+            }
+            NumberRound::Last => {
+                self.lo += i64::from(chosen);
+                self.hi = self.lo;
+                false
+            }
+        };
+        self.candidates = self.candidates();
+        self.cur = 0;
+        self.on_update();
+        more
+    }
+}
+
+impl std::fmt::Display for NumberGenerator {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.number.search {
+            NumberSearch::Digits => write!(
+                f,
+                "{}",
+                String::from_utf8_lossy(&self.digits_with(&[], b'?'))
+            ),
+            NumberSearch::Binary if self.lo == self.hi => {
+                write!(
+                    f,
+                    "{}",
+                    String::from_utf8_lossy(&self.number.format(self.lo))
+                )
+            }
+            NumberSearch::Binary => write!(
+                f,
+                "[{}, {}]",
+                String::from_utf8_lossy(&self.number.format(self.lo)),
+                String::from_utf8_lossy(&self.number.format(self.hi))
+            ),
+        }
+    }
+}
+
+impl Events for NumberGenerator {
+    fn on_update(&self) {
+        info!("number: {}", self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        pack, GenItem, Input, NumberDest, NumberGenerator, NumberInput, NumberRound, NumberSearch,
+        StdinCharGenerator, StringType, Update,
+    };
+    use crate::charset::Charset;
+
+    #[test]
+    fn widened_chunk_restored() {
+        let input = Input {
+            stdinlen: Some(4),
+            ..Input::default()
+        };
+        let charset = Charset::parse_from_arg("ab").unwrap();
+        let mut gen = StdinCharGenerator::new(input.clone(), charset);
+        assert_eq!(gen.clone().count(), 2);
+
+        assert!(gen.no_signal());
+        assert_eq!(gen.clone().count(), 4);
+        assert!(!gen.no_signal());
+
+        assert!(gen.update(pack(b"ba")));
+        assert_eq!(gen.get_input(), b"ba");
+        assert_eq!(gen.clone().count(), 2);
+
+        let mut gen = StdinCharGenerator::new(input, Charset::printable());
+        assert!(!gen.no_signal());
+    }
+
+    /// Texts of the candidates of the current round
+    fn texts(gen: &NumberGenerator) -> Vec<StringType> {
+        gen.candidates
+            .iter()
+            .map(|(_, text)| text.clone())
+            .collect()
+    }
+
+    #[test]
+    fn number_digits() {
+        let mut gen = NumberGenerator::new(&Input::new(), NumberInput::default());
+        assert_eq!(texts(&gen), vec![b"1".to_vec(), b"-1".to_vec()]);
+        let (_, input) = gen.next().unwrap();
+        assert_eq!(input.stdin, Some(b"1\n".to_vec()));
+
+        assert!(gen.update(0));
+        assert_eq!(texts(&gen).len(), 10);
+        assert_eq!(texts(&gen)[1], b"11");
+        assert!(gen.update(2));
+        assert_eq!(texts(&gen)[4], b"40");
+        assert!(gen.update(GenItem::from(b'4')));
+        assert_eq!(texts(&gen)[2], b"42");
+        assert!(!gen.update(GenItem::from(b'2')));
+        assert_eq!(gen.to_string(), "42");
+    }
+
+    #[test]
+    fn number_hex() {
+        let number = NumberInput {
+            radix: 16,
+            min: 0,
+            max: 0xff,
+            ..NumberInput::default()
+        };
+        let mut gen = NumberGenerator::new(&Input::new(), number);
+        assert_eq!(texts(&gen), vec![b"1".to_vec(), b"11".to_vec()]);
+        assert!(gen.update(2));
+        assert_eq!(texts(&gen).len(), 16);
+        assert_eq!(texts(&gen)[0xa], b"a0");
+        assert!(gen.update(GenItem::from(b'f')));
+        assert!(!gen.update(GenItem::from(b'e')));
+        assert_eq!(gen.to_string(), "fe");
+    }
+
+    #[test]
+    fn number_negative() {
+        let number = NumberInput {
+            min: -1000,
+            max: 1000,
+            ..NumberInput::default()
+        };
+        let mut gen = NumberGenerator::new(&Input::new(), number);
+        assert!(gen.update(1));
+        assert_eq!(texts(&gen)[2], b"-111");
+        assert!(gen.update(3));
+        assert_eq!(texts(&gen)[0], b"-000");
+        for &digit in b"12" {
+            assert!(gen.update(GenItem::from(digit)));
+        }
+        assert!(!gen.update(GenItem::from(b'3')));
+        assert_eq!(gen.to_string(), "-123");
+    }
+
+    #[test]
+    fn number_binary() {
+        let number = NumberInput {
+            dest: NumberDest::Arg(0),
+            search: NumberSearch::Binary,
+            min: 0,
+            max: 100,
+            ..NumberInput::default()
+        };
+        let mut gen = NumberGenerator::new(&Input::new(), number);
+        assert_eq!(
+            texts(&gen),
+            vec![b"0".to_vec(), b"50".to_vec(), b"100".to_vec()]
+        );
+
+        let target = 70;
+        while gen.round == NumberRound::Range {
+            let mid = gen.mid();
+            let chosen = if target < mid { 0 } else { 2 };
+            assert!(gen.update(chosen));
+        }
+        assert_eq!(gen.round, NumberRound::Last);
+        assert!(gen.hi - gen.lo < super::NUMBER_LAST_ROUND);
+
+        let (item, _) = gen
+            .find(|(_, input)| input.argv == Some(vec![b"70".to_vec()]))
+            .unwrap();
+        assert!(!gen.update(item));
+        assert_eq!(gen.to_string(), "70");
+    }
+}
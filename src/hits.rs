@@ -0,0 +1,112 @@
+use crate::binary::Binary;
+use crate::brute::*;
+use crate::errors::*;
+use crate::measurement::Measurement;
+use crate::process::{Process, PtraceMode};
+use crate::IS_X86;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Counts how many times the target reaches a set of addresses, such as the
+/// target of the branch taken on a correct byte. The count does not depend
+/// on noise, but breakpoints only work on x86.
+pub struct HitSolver {
+    /// Symbol names or addresses (hex) to watch. For PIE binaries, the
+    /// addresses are relative to the executable base.
+    targets: Vec<String>,
+    /// Resolved addresses of the last binary that was run
+    addrs: Mutex<Option<(PathBuf, Vec<usize>)>>,
+}
+
+impl HitSolver {
+    pub fn new(targets: Vec<String>) -> HitSolver {
+        HitSolver {
+            targets,
+            addrs: Mutex::new(None),
+        }
+    }
+
+    /// Parse a comma separated list of symbol names and addresses
+    pub fn parse_from_arg(arg: &str) -> SolverResult<Self> {
+        debug!("Executing parse_from_arg:");
+        let targets = arg
+            .split(',')
+            .filter(|target| !target.is_empty())
+            .map(String::from)
+            .collect::<Vec<String>>();
+
+        if targets.is_empty() {
+            return Err(SolverError::new(
+                Runner::ArgError,
+                "No addresses to count hits of",
+            ));
+        }
+
+        Ok(HitSolver::new(targets))
+    }
+
+    /// Resolve the targets in a binary, looking up symbols first and falling
+    /// back to hex addresses
+    fn get_addrs(&self, path: &Path) -> Result<Vec<usize>, SolverError> {
+        debug!("Executing get_addrs:");
+        let mut cache = self.addrs.lock().unwrap();
+        if let Some((cached_path, addrs)) = &*cache {
+            if cached_path == path {
+                return Ok(addrs.clone());
+            }
+        }
+
+        let binary = Binary::new(path)?;
+        let addrs = self
+            .targets
+            .iter()
+            .map(|target| {
+                binary.symbol_addr(target).or_else(|_| {
+                    usize::from_str_radix(target, 0x10).map_err(|_| {
+                        SolverError::new(
+                            Runner::ArgError,
+                            &format!("{} is not a symbol or an address", target),
+                        )
+                    })
+                })
+            })
+            .collect::<SolverResult<Vec<usize>>>()?;
+        *cache = Some((path.to_path_buf(), addrs.clone()));
+
+        Ok(addrs)
+    }
+}
+
+impl InstCounter for HitSolver {
+    /// runs the process under ptrace with a breakpoint on every watched address
+    ///
+    /// # Return
+    /// * number of times the watched addresses were reached or error
+    fn get_inst_count(&self, data: &InstCountData) -> Result<Measurement, SolverError> {
+        debug!("Executing get_inst_count:");
+        if !IS_X86 {
+            return Err(SolverError::new(
+                Runner::ArgError,
+                "Breakpoints only work on x86",
+            ));
+        }
+        if data.drop_ptrace {
+            return Err(SolverError::new(
+                Runner::ArgError,
+                "ptrace dropping and hit counting are mutually exclusive",
+            ));
+        }
+
+        let mut process = Process::new(&data.path)?;
+        process.input(&data.inp)?;
+        process.with_ptrace_mode(PtraceMode::Always);
+        process.watch(self.get_addrs(&data.path)?);
+
+        let mut handle = process.spawn();
+        let report = handle.finish(data.timeout)?;
+        trace!("hits: {:?}", report.hits);
+
+        let hits = report.hits.values().sum::<u64>() as i64;
+        Ok(report.measurement(hits).with("hits", hits))
+    }
+}
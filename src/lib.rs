@@ -7,22 +7,42 @@ extern crate log;
 extern crate env_logger;
 
 pub mod b7tui;
+pub mod beam;
 pub mod binary;
 pub mod bindings;
 pub mod brute;
+pub mod charset;
+pub mod cmphook;
+pub mod composite;
+pub mod conversation;
+pub mod coverage;
 #[cfg(feature = "dynamorio")]
 pub mod dynamorio;
 pub mod errors;
+pub mod forkserver;
 pub mod generators;
+pub mod hits;
+pub mod measurement;
 pub mod perf;
 pub mod process;
+pub mod snapshot;
+pub mod socket;
 pub mod statistics;
+pub mod step;
+pub mod syscall;
 
 use crate::b7tui::Ui;
-use crate::brute::{brute, InstCounter};
+use crate::brute::{brute, InstCounter, Sampling};
+use crate::charset::Charset;
 use crate::errors::*;
+use crate::forkserver::ForkPoint;
 use crate::generators::*;
+use crate::measurement::Selector;
+use crate::perf::PerfEvent;
+use crate::process::{CountRange, FixedEnv};
+use crate::statistics::{MedianMad, NoiseFloor, OutlierDetector};
 use std::collections::HashMap;
+use std::fmt::Display;
 use std::path::Path;
 use std::path::PathBuf;
 use std::time::Duration;
@@ -63,8 +83,69 @@ pub struct B7Opts {
     /// Whether to brute force stdin (default: `false`)
     solve_stdin: bool,
 
-    /// Which solver to use (default: `Box::new(b7::perf::PerfSolver)`)
-    solver: Box<dyn InstCounter>,
+    /// Names of environment variables to brute force (default: `vec![]`)
+    solve_env: Vec<String>,
+
+    /// Whether to brute force the files whose paths replace the `@@`
+    /// arguments (default: `false`)
+    solve_files: bool,
+
+    /// Integer to brute force with a `NumberGenerator` (default: `None`)
+    solve_number: Option<NumberInput>,
+
+    /// Candidates for each byte of brute forced input (default: `None`,
+    /// printable ASCII for argv, stdin, environment variables and files, and
+    /// every byte for memory and registers)
+    charset: Option<Charset>,
+
+    /// Bytes brute forced per round for stdin, environment variables, files,
+    /// memory and registers, up to `generators::MAX_CHUNK` (default: `1`,
+    /// falling back to 2 for stdin if a round shows no outlier)
+    chunk: usize,
+
+    /// Branches the beam search keeps per round, or `None` to commit to the
+    /// outlier of every round (default: `None`)
+    beam: Option<usize>,
+
+    /// Which solver to use (default: `None`, a `b7::perf::PerfSolver` counting
+    /// `perf_events`)
+    #[setters(skip)]
+    solver: Option<Box<dyn InstCounter>>,
+
+    /// Events counted by the default solver (default: `vec![PerfEvent::default()]`)
+    perf_events: Vec<PerfEvent>,
+
+    /// Address range the default solver counts in (default: `None`)
+    count_range: Option<CountRange>,
+
+    /// Where the default solver forks inputs from, instead of spawning the
+    /// target for each of them (default: `None`)
+    forkserver: Option<ForkPoint>,
+
+    /// Fixed environment the default solver runs the target in, or `None` for
+    /// the environment of b7 (default: `Some(FixedEnv::default())`)
+    fixed_env: Option<FixedEnv>,
+
+    /// Whether the default solver runs memory input in children forked from
+    /// the target stopped at the memory input breakpoint (default: `false`)
+    mem_snapshot: bool,
+
+    /// Chooses the value of each measurement to find outliers in (default:
+    /// `Selector::Progress`)
+    selector: Selector,
+
+    /// Picks the outlier of each round and says how sure it is (default:
+    /// `Box::new(b7::statistics::MedianMad)`)
+    detector: Box<dyn OutlierDetector>,
+
+    /// How rounds the detector is not sure about are re-run, or `None` to
+    /// run every input a fixed number of times (default: `None`)
+    sampling: Option<Sampling>,
+
+    /// Runs of the initial input to measure the noise of the solver with
+    /// before solving, or `None` to not measure it. Rounds whose outlier is
+    /// within the noise are reported as having no signal (default: `None`)
+    calibration: Option<u32>,
 
     /// Which UI to use (default: `Box::new(b7::b7tui::Env::new()`)
     ui: Box<dyn Ui>,
@@ -85,23 +166,127 @@ impl B7Opts {
             drop_ptrace: false,
             solve_argv: false,
             solve_stdin: false,
-            solver: Box::new(perf::PerfSolver),
+            solve_env: Vec::new(),
+            solve_files: false,
+            solve_number: None,
+            charset: None,
+            chunk: 1,
+            beam: None,
+            solver: None,
+            perf_events: vec![PerfEvent::default()],
+            count_range: None,
+            forkserver: None,
+            fixed_env: Some(FixedEnv::default()),
+            mem_snapshot: false,
+            selector: Selector::default(),
+            detector: Box::new(MedianMad),
+            sampling: None,
+            calibration: None,
             ui: Box::new(b7tui::Env::new()),
             vars: HashMap::new(),
             timeout: Duration::from_secs(1),
         }
     }
 
+    /// Which solver to use instead of the default `PerfSolver`
+    pub fn solver(mut self, solver: Box<dyn InstCounter>) -> Self {
+        self.solver = Some(solver);
+        self
+    }
+
+    /// Build the `PerfSolver` used when no solver was given
+    fn default_solver(&self) -> perf::PerfSolver {
+        let mut solver =
+            perf::PerfSolver::new(self.perf_events.clone()).fixed_env(self.fixed_env.clone());
+        if let Some(range) = self.count_range {
+            solver = solver.range(range);
+        }
+        if let Some(point) = self.forkserver {
+            solver = solver.forkserver(point);
+        }
+        if self.mem_snapshot {
+            solver = solver.snapshot();
+        }
+        solver
+    }
+
     /// run b7 under given state and args
     pub fn run(&mut self) -> Result<Input, SolverError> {
         debug!("Executing run: {:?}", self.init_input);
         let mut solved = self.init_input.clone();
 
+        if self.drop_ptrace && self.count_range.is_some() {
+            return Err(SolverError::new(
+                Runner::ArgError,
+                "ptrace dropping and count ranges are mutually exclusive",
+            ));
+        }
+
+        if self.forkserver.is_some() && self.count_range.is_some() {
+            return Err(SolverError::new(
+                Runner::ArgError,
+                "the forkserver and count ranges are mutually exclusive",
+            ));
+        }
+
+        if self.forkserver.is_some() && self.mem_snapshot {
+            return Err(SolverError::new(
+                Runner::ArgError,
+                "the forkserver and memory snapshots are mutually exclusive",
+            ));
+        }
+
+        if self.mem_snapshot && self.count_range.is_some() {
+            return Err(SolverError::new(
+                Runner::ArgError,
+                "memory snapshots and count ranges are mutually exclusive",
+            ));
+        }
+
+        let text_charset = self.charset.clone().unwrap_or_else(Charset::printable);
+        let byte_charset = self.charset.clone().unwrap_or_else(Charset::bytes);
+
+        let default_solver;
+        let solver: &dyn InstCounter = match self.solver {
+            Some(ref solver) => &**solver,
+            None => {
+                default_solver = self.default_solver();
+                &default_solver
+            }
+        };
+
+        let floor = match self.calibration {
+            Some(runs) => {
+                let floor = brute::calibrate(
+                    &self.path,
+                    runs,
+                    solver,
+                    &self.selector,
+                    &self.init_input,
+                    self.timeout,
+                    self.vars.clone(),
+                    self.drop_ptrace,
+                )?;
+                match floor {
+                    Some(floor) => info!("Noise floor over {} runs: {}", runs, floor.floor),
+                    None => warn!("Too few calibration runs succeeded to measure the noise"),
+                }
+                floor
+            }
+            None => None,
+        };
+
         if self.solve_argv {
             solved = default_arg_brute(
                 &self.path,
                 &solved,
-                &*self.solver,
+                solver,
+                &self.selector,
+                &*self.detector,
+                self.sampling,
+                floor,
+                &text_charset,
+                self.beam,
                 self.vars.clone(),
                 self.timeout,
                 &mut *self.ui,
@@ -113,7 +298,80 @@ impl B7Opts {
             solved = default_stdin_brute(
                 &self.path,
                 &solved,
-                &*self.solver,
+                solver,
+                &self.selector,
+                &*self.detector,
+                self.sampling,
+                floor,
+                &text_charset,
+                self.chunk,
+                self.beam,
+                self.vars.clone(),
+                self.timeout,
+                &mut *self.ui,
+                self.drop_ptrace,
+            )?;
+        }
+
+        for name in &self.solve_env {
+            solved = default_env_brute(
+                &self.path,
+                &solved,
+                name,
+                solver,
+                &self.selector,
+                &*self.detector,
+                self.sampling,
+                floor,
+                &text_charset,
+                self.chunk,
+                self.beam,
+                self.vars.clone(),
+                self.timeout,
+                &mut *self.ui,
+                self.drop_ptrace,
+            )?;
+        }
+
+        if self.solve_files {
+            solved = default_file_brute(
+                &self.path,
+                &solved,
+                solver,
+                &self.selector,
+                &*self.detector,
+                self.sampling,
+                floor,
+                &text_charset,
+                self.chunk,
+                self.beam,
+                self.vars.clone(),
+                self.timeout,
+                &mut *self.ui,
+                self.drop_ptrace,
+            )?;
+        }
+
+        if let Some(number) = &self.solve_number {
+            if self.drop_ptrace {
+                if let NumberDest::Mem(_) = number.dest {
+                    return Err(SolverError::new(
+                        Runner::ArgError,
+                        "ptrace dropping and mem input are mutually exclusive",
+                    ));
+                }
+            }
+
+            solved = default_number_brute(
+                &self.path,
+                &solved,
+                number.clone(),
+                solver,
+                &self.selector,
+                &*self.detector,
+                self.sampling,
+                floor,
+                self.beam,
                 self.vars.clone(),
                 self.timeout,
                 &mut *self.ui,
@@ -132,7 +390,39 @@ impl B7Opts {
             solved = default_mem_brute(
                 &self.path,
                 &solved,
-                &*self.solver,
+                solver,
+                &self.selector,
+                &*self.detector,
+                self.sampling,
+                floor,
+                &byte_charset,
+                self.chunk,
+                self.beam,
+                self.vars.clone(),
+                self.timeout,
+                &mut *self.ui,
+            )?;
+        }
+
+        if self.init_input.reg.is_some() {
+            if self.drop_ptrace {
+                return Err(SolverError::new(
+                    Runner::ArgError,
+                    "ptrace dropping and register input are mutually exclusive",
+                ));
+            }
+
+            solved = default_reg_brute(
+                &self.path,
+                &solved,
+                solver,
+                &self.selector,
+                &*self.detector,
+                self.sampling,
+                floor,
+                &byte_charset,
+                self.chunk,
+                self.beam,
                 self.vars.clone(),
                 self.timeout,
                 &mut *self.ui,
@@ -151,11 +441,17 @@ impl B7Opts {
 /// solves input ranges of
 /// * `argc` - 0-5
 /// * `argvlength` - 0-20
-/// * `argvchars` - 0x20-0x7e (standard ascii char range)
+/// * `argvchars` - `charset`
 fn default_arg_brute(
     path: &Path,
     init_input: &Input,
     solver: &dyn InstCounter,
+    selector: &Selector,
+    detector: &dyn OutlierDetector,
+    sampling: Option<Sampling>,
+    floor: Option<NoiseFloor>,
+    charset: &Charset,
+    beam: Option<usize>,
     vars: HashMap<String, String>,
     timeout: Duration,
     terminal: &mut dyn b7tui::Ui,
@@ -170,6 +466,10 @@ fn default_arg_brute(
         1,
         &mut argcgen,
         solver,
+        selector,
+        detector,
+        sampling,
+        floor,
         solved,
         terminal,
         vars.clone(),
@@ -186,6 +486,10 @@ fn default_arg_brute(
                 5,
                 &mut argvlengen,
                 solver,
+                selector,
+                detector,
+                sampling,
+                floor,
                 solved,
                 terminal,
                 vars.clone(),
@@ -194,16 +498,21 @@ fn default_arg_brute(
 
             // solve argv values
             if let Some(argvlens) = init_input.argvlens.clone() {
-                let mut argvgen = ArgvGenerator::new(argc, argvlens.as_slice(), 0x20, 0x7e);
-                solved = brute(
+                let mut argvgen = ArgvGenerator::new(argc, argvlens.as_slice(), charset.clone());
+                solved = search(
                     path,
                     5,
                     &mut argvgen,
                     solver,
+                    selector,
+                    detector,
+                    sampling,
+                    floor,
                     solved,
                     terminal,
                     vars.clone(),
                     drop_ptrace,
+                    beam,
                 )?;
             }
 
@@ -217,11 +526,18 @@ fn default_arg_brute(
 ///
 /// solves input ranges of
 /// * `stdinlen` - 0-51
-/// * `stdinchars` - 0x20-0x7e
+/// * `stdinchars` - `charset`
 fn default_stdin_brute(
     path: &Path,
     init_input: &Input,
     solver: &dyn InstCounter,
+    selector: &Selector,
+    detector: &dyn OutlierDetector,
+    sampling: Option<Sampling>,
+    floor: Option<NoiseFloor>,
+    charset: &Charset,
+    chunk: usize,
+    beam: Option<usize>,
     vars: HashMap<String, String>,
     timeout: Duration,
     terminal: &mut dyn b7tui::Ui,
@@ -236,6 +552,10 @@ fn default_stdin_brute(
             1,
             &mut StdinLenGenerator::new(0, 51),
             solver,
+            selector,
+            detector,
+            sampling,
+            floor,
             solved,
             terminal,
             vars.clone(),
@@ -244,33 +564,209 @@ fn default_stdin_brute(
     }
     // solve stdin if there is stuff to solve
     if solved.stdinlen.is_some() {
-        // TODO: We should have a good way of configuring the range
         let empty = String::new();
         let stdin_input = vars.get("start").unwrap_or(&empty);
         let mut gen = if stdin_input == "" {
-            StdinCharGenerator::new(solved.clone(), 0x20, 0x7e)
+            StdinCharGenerator::new(solved.clone(), charset.clone())
         } else {
-            StdinCharGenerator::new_start(solved.clone(), 0x20, 0x7e, stdin_input.as_bytes())
+            StdinCharGenerator::new_start(solved.clone(), charset.clone(), stdin_input.as_bytes())
         };
-        return Ok(brute(
+        gen.set_chunk(chunk);
+        return Ok(search(
             path,
             1,
             &mut gen,
             solver,
+            selector,
+            detector,
+            sampling,
+            floor,
             solved.clone(),
             terminal,
             vars.clone(),
             drop_ptrace,
+            beam,
         )?);
     }
     Ok(solved)
 }
 
+/// solves the value of the environment variable `name`
+///
+/// solves input ranges of
+/// * `envlen` - 0-51
+/// * `envchars` - `charset`
+fn default_env_brute(
+    path: &Path,
+    init_input: &Input,
+    name: &str,
+    solver: &dyn InstCounter,
+    selector: &Selector,
+    detector: &dyn OutlierDetector,
+    sampling: Option<Sampling>,
+    floor: Option<NoiseFloor>,
+    charset: &Charset,
+    chunk: usize,
+    beam: Option<usize>,
+    vars: HashMap<String, String>,
+    timeout: Duration,
+    terminal: &mut dyn b7tui::Ui,
+    drop_ptrace: bool,
+) -> Result<Input, SolverError> {
+    terminal.set_timeout(timeout);
+    let name = name.as_bytes();
+    let mut solved = init_input.clone();
+    // solve the value length if there is no initial value
+    if solved.env_var(name).is_none() {
+        solved = brute(
+            path,
+            1,
+            &mut EnvLenGenerator::new(name, 0, 51),
+            solver,
+            selector,
+            detector,
+            sampling,
+            floor,
+            solved,
+            terminal,
+            vars.clone(),
+            drop_ptrace,
+        )?;
+    }
+
+    let mut gen = EnvCharGenerator::new(&solved, name, charset.clone());
+    gen.set_chunk(chunk);
+    search(
+        path,
+        1,
+        &mut gen,
+        solver,
+        selector,
+        detector,
+        sampling,
+        floor,
+        solved.clone(),
+        terminal,
+        vars,
+        drop_ptrace,
+        beam,
+    )
+}
+
+/// solves the contents of each file whose path replaces an `@@` argument
+///
+/// solves input ranges of
+/// * `filelen` - 0-51
+/// * `filechars` - `charset`
+fn default_file_brute(
+    path: &Path,
+    init_input: &Input,
+    solver: &dyn InstCounter,
+    selector: &Selector,
+    detector: &dyn OutlierDetector,
+    sampling: Option<Sampling>,
+    floor: Option<NoiseFloor>,
+    charset: &Charset,
+    chunk: usize,
+    beam: Option<usize>,
+    vars: HashMap<String, String>,
+    timeout: Duration,
+    terminal: &mut dyn b7tui::Ui,
+    drop_ptrace: bool,
+) -> Result<Input, SolverError> {
+    terminal.set_timeout(timeout);
+    let mut solved = init_input.clone();
+    for idx in 0..solved.file_count() {
+        // solve the file length if there are no initial contents
+        let has_contents = solved
+            .files
+            .as_ref()
+            .map_or(false, |files| files.len() > idx);
+        if !has_contents {
+            solved = brute(
+                path,
+                1,
+                &mut FileLenGenerator::new(&solved, idx, 0, 51),
+                solver,
+                selector,
+                detector,
+                sampling,
+                floor,
+                solved.clone(),
+                terminal,
+                vars.clone(),
+                drop_ptrace,
+            )?;
+        }
+
+        let mut gen = FileCharGenerator::new(&solved, idx, charset.clone());
+        gen.set_chunk(chunk);
+        solved = search(
+            path,
+            1,
+            &mut gen,
+            solver,
+            selector,
+            detector,
+            sampling,
+            floor,
+            solved.clone(),
+            terminal,
+            vars.clone(),
+            drop_ptrace,
+            beam,
+        )?;
+    }
+    Ok(solved)
+}
+
+/// solves an integer the target parses from stdin, argv or memory
+fn default_number_brute(
+    path: &Path,
+    init_input: &Input,
+    number: NumberInput,
+    solver: &dyn InstCounter,
+    selector: &Selector,
+    detector: &dyn OutlierDetector,
+    sampling: Option<Sampling>,
+    floor: Option<NoiseFloor>,
+    beam: Option<usize>,
+    vars: HashMap<String, String>,
+    timeout: Duration,
+    terminal: &mut dyn b7tui::Ui,
+    drop_ptrace: bool,
+) -> Result<Input, SolverError> {
+    terminal.set_timeout(timeout);
+    let mut gen = NumberGenerator::new(init_input, number);
+    search(
+        path,
+        1,
+        &mut gen,
+        solver,
+        selector,
+        detector,
+        sampling,
+        floor,
+        init_input.clone(),
+        terminal,
+        vars,
+        drop_ptrace,
+        beam,
+    )
+}
+
 /// Brute force memory regions and collect results
 fn default_mem_brute(
     path: &Path,
     init_input: &Input,
     solver: &dyn InstCounter,
+    selector: &Selector,
+    detector: &dyn OutlierDetector,
+    sampling: Option<Sampling>,
+    floor: Option<NoiseFloor>,
+    charset: &Charset,
+    chunk: usize,
+    beam: Option<usize>,
     vars: HashMap<String, String>,
     timeout: Duration,
     terminal: &mut dyn b7tui::Ui,
@@ -284,18 +780,137 @@ fn default_mem_brute(
 
     let mut solved = init_input.clone();
     for input in mem {
-        let mut gen = MemGenerator::new(input.clone());
+        let mut gen = MemGenerator::new(input.clone(), charset.clone());
+        gen.set_chunk(chunk);
 
-        solved = brute(
+        solved = search(
             path,
             1,
             &mut gen,
             solver,
+            selector,
+            detector,
+            sampling,
+            floor,
             solved.clone(),
             terminal,
             vars.clone(),
             false,
+            beam,
         )?;
     }
     Ok(solved)
 }
+
+/// Brute force registers at breakpoints and collect results
+fn default_reg_brute(
+    path: &Path,
+    init_input: &Input,
+    solver: &dyn InstCounter,
+    selector: &Selector,
+    detector: &dyn OutlierDetector,
+    sampling: Option<Sampling>,
+    floor: Option<NoiseFloor>,
+    charset: &Charset,
+    chunk: usize,
+    beam: Option<usize>,
+    vars: HashMap<String, String>,
+    timeout: Duration,
+    terminal: &mut dyn b7tui::Ui,
+) -> Result<Input, SolverError> {
+    terminal.set_timeout(timeout);
+    let reg = match &init_input.reg {
+        Some(reg) => reg.clone(),
+        None => return Err(SolverError::new(Runner::NoneError, "No register to run")),
+    };
+
+    let mut solved = init_input.clone();
+    for input in reg {
+        let mut gen = RegGenerator::new(input, charset.clone());
+        gen.set_chunk(chunk);
+
+        solved = search(
+            path,
+            1,
+            &mut gen,
+            solver,
+            selector,
+            detector,
+            sampling,
+            floor,
+            solved.clone(),
+            terminal,
+            vars.clone(),
+            false,
+            beam,
+        )?;
+    }
+    Ok(solved)
+}
+
+/// Search with `gen` like `brute`, or with a beam search keeping `beam`
+/// branches per round
+fn search<G: Generate + Display + Clone>(
+    path: &Path,
+    repeat: u32,
+    gen: &mut G,
+    solver: &dyn InstCounter,
+    selector: &Selector,
+    detector: &dyn OutlierDetector,
+    sampling: Option<Sampling>,
+    floor: Option<NoiseFloor>,
+    solved: Input,
+    terminal: &mut dyn b7tui::Ui,
+    vars: HashMap<String, String>,
+    drop_ptrace: bool,
+    beam: Option<usize>,
+) -> Result<Input, SolverError> {
+    let width = match beam {
+        Some(width) => width,
+        None => {
+            return brute(
+                path,
+                repeat,
+                gen,
+                solver,
+                selector,
+                detector,
+                sampling,
+                floor,
+                solved,
+                terminal,
+                vars,
+                drop_ptrace,
+            )
+        }
+    };
+
+    let report = beam::beam(
+        path,
+        repeat,
+        gen,
+        solver,
+        selector,
+        detector,
+        sampling,
+        floor,
+        solved,
+        terminal,
+        vars,
+        drop_ptrace,
+        width,
+    )?;
+    if !report.pruned.is_empty() {
+        info!("beam search pruned {} branches:", report.pruned.len());
+        for pruned in &report.pruned {
+            info!(
+                "  {} in round {}: {}",
+                pruned.branch, pruned.round, pruned.reason
+            );
+        }
+    }
+    if report.extra_runs > 0 {
+        info!("{} extra runs to reach confidence", report.extra_runs);
+    }
+    Ok(report.input)
+}
@@ -1,16 +1,23 @@
 extern crate env_logger;
 extern crate log;
 
-use b7::brute::InstCounter;
+use b7::brute::{InstCounter, Sampling};
+use b7::charset::Charset;
+use b7::conversation::Conversation;
 use b7::errors::*;
-use b7::generators::Input;
-use b7::generators::MemInput;
+use b7::forkserver::ForkPoint;
+use b7::generators::{Input, FILE_PLACEHOLDER};
+use b7::generators::{MemInput, NumberDest, NumberInput, RegInput};
+use b7::measurement::Selector;
+use b7::process::{CountRange, Coverage, FixedEnv};
+use b7::socket::SocketTarget;
 use b7::*;
 
 use clap::{App, Arg};
 use log::debug;
 use std::collections::HashMap;
 use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
 use std::process::exit;
 use std::time::Duration;
 
@@ -25,6 +32,105 @@ fn mem_inputs_from_args(matches: &clap::ArgMatches) -> SolverResult<Option<Vec<M
     }
 }
 
+/// Parse register inputs from args
+fn reg_inputs_from_args(matches: &clap::ArgMatches) -> SolverResult<Option<Vec<RegInput>>> {
+    debug!("Executing reg_inputs_from_args:");
+    match matches.values_of("reg-brute") {
+        Some(x) => x
+            .map(RegInput::parse_from_arg)
+            .collect::<SolverResult<_>>()
+            .map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Build the solver called `name`, configured from args
+fn solver_from_args(name: &str, matches: &clap::ArgMatches) -> SolverResult<Box<dyn InstCounter>> {
+    debug!("Executing solver_from_args:");
+    if name.contains('+') {
+        let solvers = name
+            .split('+')
+            .map(|name| solver_from_args(name, matches))
+            .collect::<SolverResult<Vec<_>>>()?;
+        let combine = match matches.value_of("combine") {
+            Some(x) => composite::Combine::parse_from_arg(x)?,
+            None => composite::Combine::default(),
+        };
+        return Ok(Box::new(composite::CompositeSolver::new(solvers, combine)?));
+    }
+
+    let count_range = match matches.value_of("count-range") {
+        Some(x) => Some(CountRange::parse_from_arg(x)?),
+        None => None,
+    };
+
+    let solver = match name {
+        "perf" => {
+            let perf_events = match matches.value_of("perf-events") {
+                Some(x) => perf::PerfEvent::parse_list(x)?,
+                None => vec![perf::PerfEvent::default()],
+            };
+            let mut solver = perf::PerfSolver::new(perf_events);
+            if let Some(range) = count_range {
+                solver = solver.range(range);
+            }
+            if let Some(x) = matches.value_of("forkserver") {
+                solver = solver.forkserver(ForkPoint::parse_from_arg(x)?);
+            }
+            if matches.is_present("no-fixed-env") {
+                solver = solver.fixed_env(None);
+            } else if let Some(cwd) = matches.value_of("cwd") {
+                solver = solver.fixed_env(Some(FixedEnv {
+                    cwd: Some(cwd.into()),
+                    ..FixedEnv::default()
+                }));
+            }
+            if matches.is_present("mem-snapshot") {
+                solver = solver.snapshot();
+            }
+            Box::new(solver) as Box<dyn InstCounter>
+        }
+        "step" => {
+            let mut solver = step::StepSolver::new();
+            if let Some(range) = count_range {
+                solver = solver.range(range);
+            }
+            Box::new(solver) as Box<dyn InstCounter>
+        }
+        "syscall" => {
+            let mut solver = syscall::SyscallSolver::new();
+            if let Some(nr) = matches.value_of("syscall-nr") {
+                let nr = nr
+                    .parse()
+                    .map_err(|_| SolverError::new(Runner::ArgError, "Invalid syscall number"))?;
+                solver = solver.syscall(nr);
+            }
+            Box::new(solver) as Box<dyn InstCounter>
+        }
+        "hits" => match matches.value_of("hit-addrs") {
+            Some(x) => Box::new(hits::HitSolver::parse_from_arg(x)?) as Box<dyn InstCounter>,
+            None => {
+                return Err(SolverError::new(
+                    Runner::ArgError,
+                    "The hits solver needs --hit-addrs",
+                ))
+            }
+        },
+        "cmphook" => Box::new(cmphook::CmpHookSolver::new()) as Box<dyn InstCounter>,
+        "coverage" => {
+            Box::new(coverage::CoverageSolver::new(Coverage::Blocks)) as Box<dyn InstCounter>
+        }
+        "edge-coverage" => {
+            Box::new(coverage::CoverageSolver::new(Coverage::Edges)) as Box<dyn InstCounter>
+        }
+        #[cfg(feature = "dynamorio")]
+        "dynamorio" => Box::new(dynamorio::DynamorioSolver) as Box<dyn InstCounter>,
+        _ => panic!("unknown solver"),
+    };
+
+    Ok(solver)
+}
+
 /// parses program arguements
 fn handle_cli_args<'a>() -> clap::ArgMatches<'a> {
     debug!("Executing handle_cli_args:");
@@ -38,7 +144,10 @@ fn handle_cli_args<'a>() -> clap::ArgMatches<'a> {
         )
         .arg(
             Arg::with_name("args")
-                .help("Initial arguments to binary")
+                .help(
+                    "Initial arguments to binary. Each `@@` is replaced with \
+                     the path of a file whose contents are brute forced",
+                )
                 .multiple(true),
         )
         .arg(
@@ -46,7 +155,109 @@ fn handle_cli_args<'a>() -> clap::ArgMatches<'a> {
                 .short("s")
                 .long("solver")
                 .value_name("solver")
-                .help("Sets which solver to use (default perf)")
+                .help(
+                    "Sets which solver to use: perf, step, syscall, hits, \
+                     cmphook, coverage or edge-coverage (default perf). \
+                     Join solvers with `+` to combine them, e.g. perf+syscall",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("combine")
+                .long("combine")
+                .value_name("combination")
+                .help(
+                    "How to combine the results of joined solvers: sum[=weights], \
+                     lex (ordered by the first solver, ties broken by the next) \
+                     or first (the first solver that shows a signal) (default lex)\
+                     \n    Example: `--solver syscall+perf --combine sum=1000,1`",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("select")
+                .long("select")
+                .value_name("selector")
+                .help(
+                    "Which measurement to find the outlier in: progress (the \
+                     solver's own value), a metric such as instructions, \
+                     exit_status, wall_time or stdout_len, or a weighted sum \
+                     of them (default progress)\
+                     \n    Example: `--select instructions+1000*exit_status`",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("outlier")
+                .long("outlier")
+                .value_name("detector")
+                .help(
+                    "How to pick the outlier of each round: mad (furthest from \
+                     the median), zscore (furthest from the mean) or gap (by \
+                     its lead over the runner-up) (default mad)",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("direction")
+                .long("direction")
+                .value_name("direction")
+                .help(
+                    "Which way the outlier differs from the other inputs: \
+                     higher, lower, any, or auto to learn it from the first \
+                     confident rounds and hold it (default any)",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("adaptive")
+                .long("adaptive")
+                .value_name("sampling")
+                .help(
+                    "Run each input once and re-run the top contenders of a \
+                     round until the outlier detector reaches a confidence, \
+                     at most max times (default confidence=0.95,max=10,top=3)\
+                     \n    Example: `--adaptive confidence=0.99,top=5`",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("calibrate")
+                .long("calibrate")
+                .value_name("runs")
+                .help(
+                    "Run the initial input this many times before solving to \
+                     measure the noise of the solver, and report rounds whose \
+                     outlier is within it as having no signal",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("perf-events")
+                .long("perf-events")
+                .value_name("events")
+                .help(
+                    "Comma separated perf events for the perf solver to count \
+                     as one group, by `perf list` name or raw `rXXXX`. The \
+                     first event is the progress, `--select` picks the others \
+                     (default instructions)\
+                     \n    Example: `--perf-events instructions,branches,r20c4`",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("forkserver")
+                .long("forkserver")
+                .value_name("point")
+                .conflicts_with("count-range")
+                .help(
+                    "Run each input in a child forked from the target at main or \
+                     at an address (hex, relative to the executable base for PIE \
+                     binaries) instead of spawning it, with the perf solver. \
+                     The target must be dynamically linked, and argv input needs \
+                     forking at main.\
+                     \n    Example: `--forkserver main`",
+                )
                 .takes_value(true),
         )
         .arg(
@@ -92,6 +303,42 @@ fn handle_cli_args<'a>() -> clap::ArgMatches<'a> {
                 .help("per-thread timeout to use when waiting for results, in seconds")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("socket")
+                .long("socket")
+                .value_name("address")
+                .help(
+                    "Send the stdin input to the target once it listens on a \
+                     localhost TCP or UDP port or a unix socket, instead of \
+                     its stdin. Targets then run one at a time.\
+                     \n    Example: `--socket tcp:4444`",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("conversation")
+                .long("conversation")
+                .value_name("script")
+                .conflicts_with("socket")
+                .help(
+                    "Script of `expect REGEX`, `send TEXT` and `input` lines \
+                     to answer the prompts of an interactive target with. \
+                     The stdin input is sent at the `input` step.",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("env-brute")
+                .long("env-brute")
+                .value_name("name")
+                .help(
+                    "Name of an environment variable to brute force the value \
+                     of, can be given more than once\
+                     \n    Example: `--env-brute SECRET`",
+                )
+                .takes_value(true)
+                .multiple(true),
+        )
         .arg(
             Arg::with_name("mem-brute")
                 .long("mem-brute")
@@ -106,10 +353,138 @@ fn handle_cli_args<'a>() -> clap::ArgMatches<'a> {
                 .takes_value(true)
                 .multiple(true),
         )
+        .arg(
+            Arg::with_name("charset")
+                .long("charset")
+                .value_name("chars")
+                .help(
+                    "Candidates for each brute forced byte: hex, alnum, \
+                     printable, bytes, or a custom set with ranges like \
+                     `a-z0-9_{}`. Defaults to printable for argv, stdin, \
+                     environment variables and files and to bytes for memory \
+                     and registers.",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("chunk")
+                .long("chunk")
+                .value_name("bytes")
+                .help(
+                    "Bytes to brute force per round, up to 4, for targets \
+                     that check their input several bytes at a time. Every \
+                     combination of the charset is tried, so pair it with a \
+                     small --charset. Stdin falls back to 2 on its own when a \
+                     round shows no outlier and the charset has at most 64 \
+                     bytes.",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("beam")
+                .long("beam")
+                .value_name("width")
+                .help(
+                    "Keep this many candidates per position instead of only \
+                     the best one, and backtrack to them when later rounds \
+                     show no outlier",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("number-brute")
+                .long("number-brute")
+                .value_name("spec")
+                .help(
+                    "Brute force an integer the target parses with scanf, \
+                     atoi or strtol, instead of its characters. All options \
+                     are optional: `in` is stdin, argvN or mem (with the \
+                     addr, size and breakpoint options of --mem-brute), \
+                     `base` is 10 or 16, `search` is digits, or binary for \
+                     targets comparing with < and >, and `min` and `max` \
+                     bound the number in decimal.\
+                     \n    Example: `--number-brute \
+                     in=argv1,search=binary,min=0,max=100000`",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("reg-brute")
+                .long("reg-brute")
+                .help(
+                    "Breakpoint address, register, size in bytes and initial \
+                     input (optional, least significant byte first) of a \
+                     register to brute force. The register is set when the \
+                     target first reaches the breakpoint, e.g. right after a \
+                     call whose return value is checked. x86_64 only.\
+                     \n    Example: `--reg-brute bp=4011a0,reg=rax,size=4`",
+                )
+                .takes_value(true)
+                .multiple(true),
+        )
+        .arg(Arg::with_name("no-fixed-env").long("no-fixed-env").help(
+            "Run the target with ASLR, the environment of b7 and its \
+                     path as argv[0], instead of the fixed environment the \
+                     perf solver uses by default",
+        ))
+        .arg(
+            Arg::with_name("cwd")
+                .long("cwd")
+                .value_name("dir")
+                .conflicts_with("no-fixed-env")
+                .help("Working directory of the target with the perf solver")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("mem-snapshot")
+                .long("mem-snapshot")
+                .requires("mem-brute")
+                .conflicts_with("forkserver")
+                .conflicts_with("count-range")
+                .help(
+                    "Run the target once up to the memory input breakpoint and \
+                     fork it there for each input, with the perf solver. All \
+                     memory inputs must share one breakpoint. x86_64 only.",
+                ),
+        )
+        .arg(
+            Arg::with_name("syscall-nr")
+                .long("syscall-nr")
+                .value_name("nr")
+                .help("Only count calls of this syscall number with the syscall solver")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("hit-addrs")
+                .long("hit-addrs")
+                .value_name("addrs")
+                .help(
+                    "Comma separated symbol names or addresses for the hits \
+                     solver to count reaching. For PIE binaries, the addresses \
+                     are relative to the executable base. Otherwise, the \
+                     addresses are absolute.\
+                     \n    Example: `--hit-addrs 4011a0,check_char`",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("count-range")
+                .long("count-range")
+                .help(
+                    "Only count between a start and a stop address (either \
+                     may be left out). For PIE binaries, the addresses are \
+                     relative to the executable base. Otherwise, the \
+                     addresses are absolute.\
+                     \n    Example: `--count-range start=4011a0,stop=401230`",
+                )
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("drop-ptrace")
                 .long("drop-ptrace")
                 .conflicts_with("mem-brute")
+                .conflicts_with("reg-brute")
+                .conflicts_with("count-range")
                 .help(
                     "detach from ptrace after the binary starts (use this if the \
                      binary is movfuscated, has frequently-triggered signal \
@@ -145,20 +520,62 @@ fn main() -> Result<(), SolverError> {
         None => None,
     };
 
+    let solve_files = args.as_ref().map_or(false, |args: &Vec<Vec<u8>>| {
+        args.iter().any(|arg| arg.as_slice() == FILE_PLACEHOLDER)
+    });
+    let number = match matches.value_of("number-brute") {
+        Some(x) => Some(NumberInput::parse_from_arg(x)?),
+        None => None,
+    };
+    let number_dest = number.as_ref().map(|number| &number.dest);
     let drop_ptrace = matches.is_present("drop-ptrace");
-    let argstate = matches.occurrences_of("argstate") < 1;
-    let stdinstate = matches.occurrences_of("stdinstate") < 1;
+    // argv with `@@` is fixed, only the files are brute forced. The argument
+    // or stdin holding a number is only brute forced as a number.
+    let number_in_argv = match number_dest {
+        Some(NumberDest::Arg(_)) => true,
+        _ => false,
+    };
+    let argstate = matches.occurrences_of("argstate") < 1 && !solve_files && !number_in_argv;
+    let stdinstate =
+        matches.occurrences_of("stdinstate") < 1 && number_dest != Some(&NumberDest::Stdin);
     let stdinlen = match matches.value_of("stdin-len") {
         Some(x) => Some(x.parse::<u32>().expect("invalid stdin length")),
         None => None,
     };
 
     let solvername = matches.value_of("solver").unwrap_or("perf");
-    let solver = match solvername {
-        "perf" => Box::new(perf::PerfSolver) as Box<dyn InstCounter>,
-        #[cfg(feature = "dynamorio")]
-        "dynamorio" => Box::new(dynamorio::DynamorioSolver) as Box<dyn InstCounter>,
-        _ => panic!("unknown solver"),
+    let solver = solver_from_args(solvername, &matches)?;
+    let charset = match matches.value_of("charset") {
+        Some(x) => Some(Charset::parse_from_arg(x)?),
+        None => None,
+    };
+    let chunk = match matches.value_of("chunk") {
+        Some(x) => x.parse::<usize>().expect("invalid chunk size"),
+        None => 1,
+    };
+    let beam = match matches.value_of("beam") {
+        Some(x) => Some(x.parse::<usize>().expect("invalid beam width")),
+        None => None,
+    };
+    let selector = match matches.value_of("select") {
+        Some(x) => Selector::parse_from_arg(x)?,
+        None => Selector::default(),
+    };
+    let detector = match matches.value_of("outlier") {
+        Some(x) => statistics::detector_from_arg(x)?,
+        None => Box::new(statistics::MedianMad),
+    };
+    let detector = match matches.value_of("direction") {
+        Some(x) => Box::new(statistics::Directed::parse_from_arg(detector, x)?),
+        None => detector,
+    };
+    let sampling = match matches.value_of("adaptive") {
+        Some(x) => Some(Sampling::parse_from_arg(x)?),
+        None => None,
+    };
+    let calibration = match matches.value_of("calibrate") {
+        Some(x) => Some(x.parse::<u32>().expect("invalid calibration runs")),
+        None => None,
     };
     let timeout = Duration::from_secs(
         matches
@@ -186,12 +603,36 @@ fn main() -> Result<(), SolverError> {
             stdinlen,
             argv: args,
             mem: mem_inputs_from_args(&matches)?,
+            reg: reg_inputs_from_args(&matches)?,
+            socket: match matches.value_of("socket") {
+                Some(x) => Some(SocketTarget::parse_from_arg(x)?),
+                None => None,
+            },
+            conversation: match matches.value_of("conversation") {
+                Some(x) => Some(Conversation::from_file(Path::new(x))?),
+                None => None,
+            },
             ..Default::default()
         })
         .drop_ptrace(drop_ptrace)
         .solve_argv(argstate)
         .solve_stdin(stdinstate)
+        .solve_files(solve_files)
+        .solve_number(number)
+        .charset(charset)
+        .chunk(chunk)
+        .beam(beam)
+        .solve_env(
+            matches
+                .values_of("env-brute")
+                .map(|names| names.map(String::from).collect())
+                .unwrap_or_default(),
+        )
         .solver(solver)
+        .selector(selector)
+        .detector(detector)
+        .sampling(sampling)
+        .calibration(calibration)
         .ui(ui)
         .vars(vars)
         .timeout(timeout)
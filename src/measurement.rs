@@ -0,0 +1,173 @@
+use crate::errors::*;
+use std::time::Duration;
+
+/// Exit code of the process, or 128 plus the signal that killed it
+pub const EXIT_STATUS: &str = "exit_status";
+/// Time from starting to wait for the process until it exited, in microseconds
+pub const WALL_TIME: &str = "wall_time";
+/// Bytes the process wrote to stdout
+pub const STDOUT_LEN: &str = "stdout_len";
+
+/// Everything a solver observed while running the target once. `progress` is
+/// the solver's own measure of progress, and `metrics` holds named values
+/// such as `instructions`, `branches` or `exit_status` that selectors can
+/// choose from or combine.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Measurement {
+    pub progress: i64,
+    metrics: Vec<(String, i64)>,
+}
+
+impl Measurement {
+    pub fn new(progress: i64) -> Measurement {
+        Measurement {
+            progress,
+            metrics: Vec::new(),
+        }
+    }
+
+    /// Set the metric `name`, replacing its old value
+    pub fn set(&mut self, name: &str, value: i64) {
+        match self.metrics.iter_mut().find(|(metric, _)| metric == name) {
+            Some(metric) => metric.1 = value,
+            None => self.metrics.push((name.to_string(), value)),
+        }
+    }
+
+    /// Builder version of `set()`
+    pub fn with(mut self, name: &str, value: i64) -> Measurement {
+        self.set(name, value);
+        self
+    }
+
+    /// Set the metrics every run of a process has
+    pub fn with_exit(self, exit_status: i32, wall_time: Duration, stdout_len: usize) -> Self {
+        self.with(EXIT_STATUS, i64::from(exit_status))
+            .with(WALL_TIME, wall_time.as_micros() as i64)
+            .with(STDOUT_LEN, stdout_len as i64)
+    }
+
+    /// Get the metric `name`
+    pub fn get(&self, name: &str) -> Option<i64> {
+        self.metrics
+            .iter()
+            .find(|(metric, _)| metric == name)
+            .map(|(_, value)| *value)
+    }
+
+    /// Add the metrics of `other` that are not set yet
+    pub fn merge(&mut self, other: &Measurement) {
+        for (name, value) in other.iter() {
+            if self.get(name).is_none() {
+                self.set(name, value);
+            }
+        }
+    }
+
+    /// Iterate over the metrics in the order they were set
+    pub fn iter(&self) -> impl Iterator<Item = (&str, i64)> {
+        self.metrics
+            .iter()
+            .map(|(name, value)| (name.as_str(), *value))
+    }
+}
+
+/// Chooses the value of a `Measurement` that outliers are searched in
+#[derive(Debug, Clone, PartialEq)]
+pub enum Selector {
+    /// The progress value of the solver
+    Progress,
+    /// A single metric
+    Metric(String),
+    /// Sum of metrics, each multiplied by its weight. `progress` names the
+    /// progress value.
+    WeightedSum(Vec<(String, i64)>),
+}
+
+impl Default for Selector {
+    fn default() -> Self {
+        Selector::Progress
+    }
+}
+
+impl Selector {
+    /// Parse a selector from `progress`, a metric name, or a sum of metrics
+    /// with optional weights of the format:
+    ///
+    /// ``` text
+    /// instructions+1000*exit_status
+    /// ```
+    pub fn parse_from_arg(arg: &str) -> SolverResult<Self> {
+        debug!("Executing parse_from_arg:");
+        if arg.is_empty() {
+            return Err(SolverError::new(Runner::ArgError, "Empty selector"));
+        }
+        if arg == "progress" {
+            return Ok(Selector::Progress);
+        }
+        if !arg.contains('+') && !arg.contains('*') {
+            return Ok(Selector::Metric(arg.to_string()));
+        }
+
+        arg.split('+')
+            .map(|term| {
+                let mut parts = term.splitn(2, '*');
+                match (parts.next(), parts.next()) {
+                    (Some(name), None) => Ok((name.to_string(), 1)),
+                    (Some(weight), Some(name)) => match weight.parse() {
+                        Ok(weight) => Ok((name.to_string(), weight)),
+                        Err(_) => Err(SolverError::new(
+                            Runner::ArgError,
+                            &format!("Invalid weight {}", weight),
+                        )),
+                    },
+                    _ => unreachable!(),
+                }
+            })
+            .collect::<SolverResult<Vec<(String, i64)>>>()
+            .map(Selector::WeightedSum)
+    }
+
+    /// Get the selected value of a measurement
+    pub fn select(&self, measurement: &Measurement) -> SolverResult<i64> {
+        let get = |name: &str| {
+            if name == "progress" {
+                return Ok(measurement.progress);
+            }
+            measurement.get(name).ok_or_else(|| {
+                SolverError::new(
+                    Runner::ArgError,
+                    &format!("The solver does not measure {}", name),
+                )
+            })
+        };
+
+        match self {
+            Selector::Progress => Ok(measurement.progress),
+            Selector::Metric(name) => get(name),
+            Selector::WeightedSum(terms) => terms
+                .iter()
+                .map(|(name, weight)| get(name).map(|value| value * weight))
+                .sum(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Measurement, Selector};
+
+    #[test]
+    fn select_metrics() {
+        let measurement = Measurement::new(5)
+            .with("instructions", 100)
+            .with("exit_status", 1);
+
+        let selector = Selector::parse_from_arg("instructions+1000*exit_status").unwrap();
+        assert_eq!(selector.select(&measurement).unwrap(), 1100);
+        let selector = Selector::parse_from_arg("progress").unwrap();
+        assert_eq!(selector.select(&measurement).unwrap(), 5);
+        let selector = Selector::parse_from_arg("branches").unwrap();
+        assert!(selector.select(&measurement).is_err());
+    }
+}
@@ -1,18 +1,162 @@
 use crate::bindings::*;
 use crate::brute::*;
+use crate::errors::Runner::ArgError;
 use crate::errors::*;
-use crate::process::Process;
+use crate::forkserver::{ForkPoint, ForkServerPool};
+use crate::measurement::{Measurement, EXIT_STATUS, WALL_TIME};
 use crate::process::PtraceMode;
-use libc::{c_int, c_void, ioctl, pid_t, syscall};
-use std::ffi::OsStr;
+use crate::process::{CountGate, CountRange, FixedEnv, Process};
+use crate::snapshot::SnapshotCache;
+use libc::{c_ulong, c_void, ioctl, pid_t, syscall};
 use std::fs::File;
 use std::mem;
-use std::os::unix::ffi::OsStrExt;
 use std::os::unix::io::FromRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 /// syscall number for perf syscall
 const PERF_EVENT_OPEN_SYSCALL: i64 = 298;
 
+/// ioctl requests for perf file descriptors
+const PERF_EVENT_IOC_ENABLE: c_ulong = 9216;
+const PERF_EVENT_IOC_DISABLE: c_ulong = 9217;
+const PERF_EVENT_IOC_RESET: c_ulong = 9219;
+
+/// ioctl argument to apply a request to every event in a group
+const PERF_IOC_FLAG_GROUP: c_ulong = 1;
+
+/// `perf list` names of the supported generic hardware events
+const HARDWARE_EVENTS: &[(&str, perf_hw_id)] = &[
+    ("instructions", perf_hw_id_PERF_COUNT_HW_INSTRUCTIONS),
+    ("cycles", perf_hw_id_PERF_COUNT_HW_CPU_CYCLES),
+    ("branches", perf_hw_id_PERF_COUNT_HW_BRANCH_INSTRUCTIONS),
+    ("branch-misses", perf_hw_id_PERF_COUNT_HW_BRANCH_MISSES),
+    (
+        "cache-references",
+        perf_hw_id_PERF_COUNT_HW_CACHE_REFERENCES,
+    ),
+    ("cache-misses", perf_hw_id_PERF_COUNT_HW_CACHE_MISSES),
+    ("bus-cycles", perf_hw_id_PERF_COUNT_HW_BUS_CYCLES),
+    ("ref-cycles", perf_hw_id_PERF_COUNT_HW_REF_CPU_CYCLES),
+];
+
+/// `perf list` names of the supported software events
+const SOFTWARE_EVENTS: &[(&str, perf_sw_ids)] = &[
+    ("cpu-clock", perf_sw_ids_PERF_COUNT_SW_CPU_CLOCK),
+    ("task-clock", perf_sw_ids_PERF_COUNT_SW_TASK_CLOCK),
+    ("page-faults", perf_sw_ids_PERF_COUNT_SW_PAGE_FAULTS),
+    (
+        "context-switches",
+        perf_sw_ids_PERF_COUNT_SW_CONTEXT_SWITCHES,
+    ),
+    ("cpu-migrations", perf_sw_ids_PERF_COUNT_SW_CPU_MIGRATIONS),
+    ("minor-faults", perf_sw_ids_PERF_COUNT_SW_PAGE_FAULTS_MIN),
+    ("major-faults", perf_sw_ids_PERF_COUNT_SW_PAGE_FAULTS_MAJ),
+    (
+        "alignment-faults",
+        perf_sw_ids_PERF_COUNT_SW_ALIGNMENT_FAULTS,
+    ),
+    (
+        "emulation-faults",
+        perf_sw_ids_PERF_COUNT_SW_EMULATION_FAULTS,
+    ),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// An event that `PerfSolver` can count
+pub enum PerfEvent {
+    /// Generic hardware event, one of `perf_hw_id`
+    Hardware(u64),
+    /// Kernel software event, one of `perf_sw_ids`
+    Software(u64),
+    /// Model specific PMU event, as given to `perf stat -e rNNNN`
+    Raw(u64),
+}
+
+impl Default for PerfEvent {
+    /// Retired instructions
+    fn default() -> Self {
+        PerfEvent::Hardware(u64::from(perf_hw_id_PERF_COUNT_HW_INSTRUCTIONS))
+    }
+}
+
+impl PerfEvent {
+    /// Parse an event from its `perf list` name (`instructions`, `branches`,
+    /// `page-faults`, ...) or from a raw event of the format:
+    ///
+    /// ``` text
+    /// rXXXX
+    /// ```
+    pub fn parse_from_arg(arg: &str) -> SolverResult<Self> {
+        debug!("Executing parse_from_arg:");
+        if let Some(&(_, id)) = HARDWARE_EVENTS.iter().find(|(name, _)| *name == arg) {
+            return Ok(PerfEvent::Hardware(u64::from(id)));
+        }
+
+        if let Some(&(_, id)) = SOFTWARE_EVENTS.iter().find(|(name, _)| *name == arg) {
+            return Ok(PerfEvent::Software(u64::from(id)));
+        }
+
+        match arg.strip_prefix('r') {
+            Some(config) => {
+                let config = u64::from_str_radix(config, 0x10);
+                let config =
+                    config.map_err(|_| SolverError::new(ArgError, "Invalid raw perf event"))?;
+                Ok(PerfEvent::Raw(config))
+            }
+            None => Err(SolverError::new(
+                ArgError,
+                &format!("Unknown perf event {}", arg),
+            )),
+        }
+    }
+
+    /// Parse a comma separated list of events
+    pub fn parse_list(arg: &str) -> SolverResult<Vec<Self>> {
+        arg.split(',').map(PerfEvent::parse_from_arg).collect()
+    }
+
+    /// Name of the event as it was parsed
+    pub fn name(self) -> String {
+        let name = match self {
+            PerfEvent::Hardware(config) => HARDWARE_EVENTS
+                .iter()
+                .find(|(_, id)| u64::from(*id) == config)
+                .map(|(name, _)| *name),
+            PerfEvent::Software(config) => SOFTWARE_EVENTS
+                .iter()
+                .find(|(_, id)| u64::from(*id) == config)
+                .map(|(name, _)| *name),
+            PerfEvent::Raw(_) => None,
+        };
+
+        match (name, self) {
+            (Some(name), _) => name.to_string(),
+            (None, PerfEvent::Raw(config)) => format!("r{:x}", config),
+            (None, _) => format!("{:?}", self),
+        }
+    }
+
+    /// `perf_event_attr` type and config of the event
+    fn type_config(self) -> (u32, u64) {
+        match self {
+            PerfEvent::Hardware(config) => (perf_type_id_PERF_TYPE_HARDWARE, config),
+            PerfEvent::Software(config) => (perf_type_id_PERF_TYPE_SOFTWARE, config),
+            PerfEvent::Raw(config) => (perf_type_id_PERF_TYPE_RAW, config),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// What to do with counts the kernel had to multiplex because there were more
+/// events than hardware counters
+pub enum Multiplex {
+    /// Extrapolate the counts by `time_enabled / time_running`
+    Scale,
+    /// Fail the run
+    Reject,
+}
+
 /// initiliaze perf on a process
 ///
 /// # Arguments
@@ -35,75 +179,317 @@ fn perf_event_open(
     unsafe { syscall(PERF_EVENT_OPEN_SYSCALL, hw_event, pid, cpu, group_fd, flags) as i32 }
 }
 
-/// perform perf struct setup and clear the perf file descriptor
-fn get_perf_fd(pid: pid_t) -> Result<i32, SolverError> {
+/// perform perf struct setup for one event and open it in the group led by
+/// `group_fd` (-1 to make the event a new group leader)
+fn get_perf_fd(pid: pid_t, event: PerfEvent, group_fd: i32) -> Result<i32, SolverError> {
     debug!("Executing get_perf_fd");
     let mut pe: perf_event_attr = unsafe { mem::zeroed() };
+    let (type_, config) = event.type_config();
 
     // perf struct setup
-    pe.type_ = perf_type_id_PERF_TYPE_HARDWARE;
+    pe.type_ = type_;
     pe.size = mem::size_of::<perf_event_attr>() as u32;
-    pe.config = u64::from(perf_hw_id_PERF_COUNT_HW_INSTRUCTIONS);
-    pe.set_disabled(1);
+    pe.config = config;
+    pe.read_format = u64::from(
+        perf_event_read_format_PERF_FORMAT_GROUP
+            | perf_event_read_format_PERF_FORMAT_TOTAL_TIME_ENABLED
+            | perf_event_read_format_PERF_FORMAT_TOTAL_TIME_RUNNING,
+    );
+    // only the leader starts disabled, the other events follow it
+    pe.set_disabled(if group_fd == -1 { 1 } else { 0 });
     pe.set_exclude_kernel(1);
     pe.set_exclude_hv(1);
     pe.set_exclude_idle(1);
     pe.set_exclude_callchain_kernel(1);
 
-    let fd = perf_event_open(&pe as *const perf_event_attr, pid, -1, -1, 0);
+    let fd = perf_event_open(&pe as *const perf_event_attr, pid, -1, group_fd, 0);
     if fd == -1 {
         return Err(SolverError::new(Runner::IoError, "perf_event_open failed!"));
     }
 
-    // reset perf to make sure it is zero
-    unsafe {
-        ioctl(fd, 9219, 0); // PERF_EVENT_IOC_RESET
-        ioctl(fd, 9216, 0); // PERF_EVENT_IOC_ENABLE
-    }
     trace!("initialized perf on pid {} return fd {}", pid, fd);
     Ok(fd)
 }
 
-/// read instruction count from perf file descriptor
-fn perf_get_inst_count(fd: c_int) -> Result<i64, SolverError> {
-    debug!("Executing perf_get_inst_count:");
-    let mut count: i64 = 0;
-    match unsafe { libc::read(fd, &mut count as *mut i64 as *mut c_void, 8) as i64 } {
-        8 => Ok(count),
-        x if x >= 0 => Err(SolverError::new(
-            Runner::IoError,
-            &format!("Perf only read {} bytes!", x),
-        )),
-        _ => Err(SolverError::new(
-            Runner::IoError,
-            "Could not read from perf fd",
-        )),
+/// Scale a count up to the whole time its counter was enabled
+fn scale_count(count: u64, enabled: u64, running: u64) -> i64 {
+    (u128::from(count) * u128::from(enabled) / u128::from(running)) as i64
+}
+
+/// Perf counters on one process that are scheduled, enabled and read together
+struct PerfGroup {
+    /// Counter file descriptors, the first one is the group leader
+    fds: Vec<i32>,
+}
+
+impl PerfGroup {
+    /// open `events` on a process as one group, then reset and enable it
+    fn open(pid: pid_t, events: &[PerfEvent]) -> Result<PerfGroup, SolverError> {
+        debug!("Executing PerfGroup::open:");
+        if events.is_empty() {
+            return Err(SolverError::new(ArgError, "No perf events to count"));
+        }
+
+        let mut group = PerfGroup {
+            fds: Vec::with_capacity(events.len()),
+        };
+        for event in events {
+            let leader = group.fds.first().cloned().unwrap_or(-1);
+            group.fds.push(get_perf_fd(pid, *event, leader)?);
+        }
+
+        // reset perf to make sure it is zero
+        group.ioctl(PERF_EVENT_IOC_RESET)?;
+        group.ioctl(PERF_EVENT_IOC_ENABLE)?;
+        Ok(group)
+    }
+
+    /// apply an ioctl request to every counter in the group
+    fn ioctl(&self, request: c_ulong) -> Result<(), SolverError> {
+        match unsafe { ioctl(self.fds[0], request, PERF_IOC_FLAG_GROUP) } {
+            -1 => Err(SolverError::new(Runner::IoError, "perf ioctl failed")),
+            _ => Ok(()),
+        }
+    }
+
+    /// read the counts of the group, in the order the events were given
+    fn read(&self, multiplex: Multiplex) -> Result<Vec<i64>, SolverError> {
+        debug!("Executing PerfGroup::read:");
+        // PERF_FORMAT_GROUP layout: nr, time_enabled, time_running, values[nr]
+        let mut buf = vec![0u64; 3 + self.fds.len()];
+        let size = buf.len() * mem::size_of::<u64>();
+        match unsafe { libc::read(self.fds[0], buf.as_mut_ptr() as *mut c_void, size) as i64 } {
+            x if x == size as i64 => {}
+            x if x >= 0 => {
+                return Err(SolverError::new(
+                    Runner::IoError,
+                    &format!("Perf only read {} bytes!", x),
+                ))
+            }
+            _ => {
+                return Err(SolverError::new(
+                    Runner::IoError,
+                    "Could not read from perf fd",
+                ))
+            }
+        }
+
+        let (enabled, running) = (buf[1], buf[2]);
+        if enabled == 0 {
+            return Ok(vec![0; self.fds.len()]);
+        }
+        if running == 0 {
+            return Err(SolverError::new(
+                Runner::IoError,
+                "perf counters could not be scheduled",
+            ));
+        }
+        if running < enabled && multiplex == Multiplex::Reject {
+            return Err(SolverError::new(
+                Runner::IoError,
+                "perf counters were multiplexed",
+            ));
+        }
+
+        Ok(buf[3..]
+            .iter()
+            .map(|&count| scale_count(count, enabled, running))
+            .collect())
     }
 }
 
-#[derive(Copy, Clone)]
-pub struct PerfSolver;
+// switch the group on and off at the counted address range
+impl CountGate for PerfGroup {
+    fn enable(&self) -> SolverResult<()> {
+        self.ioctl(PERF_EVENT_IOC_ENABLE)
+    }
+
+    fn disable(&self) -> SolverResult<()> {
+        self.ioctl(PERF_EVENT_IOC_DISABLE)
+    }
+}
+
+impl Drop for PerfGroup {
+    fn drop(&mut self) {
+        for &fd in &self.fds {
+            drop(unsafe { File::from_raw_fd(fd) });
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Counts perf events while the target runs, or only inside a count range.
+/// When several events are selected they are read as one group. The count
+/// of the first event is the progress, the others are only kept as metrics
+/// named after their events, for a `Selector` to pick or weigh.
+pub struct PerfSolver {
+    events: Vec<PerfEvent>,
+    multiplex: Multiplex,
+    range: Option<CountRange>,
+    forkserver: Option<Arc<ForkServerPool>>,
+    snapshots: Option<Arc<SnapshotCache>>,
+    env: Option<FixedEnv>,
+}
+
+impl PerfSolver {
+    /// Count `events`, scaling multiplexed counts
+    pub fn new(events: Vec<PerfEvent>) -> PerfSolver {
+        PerfSolver {
+            events,
+            multiplex: Multiplex::Scale,
+            range: None,
+            forkserver: None,
+            snapshots: None,
+            env: Some(FixedEnv::default()),
+        }
+    }
+
+    /// Set what to do with multiplexed counts (default: `Multiplex::Scale`)
+    pub fn multiplex(mut self, multiplex: Multiplex) -> PerfSolver {
+        self.multiplex = multiplex;
+        self
+    }
+
+    /// Only count while execution is between the addresses of `range`
+    pub fn range(mut self, range: CountRange) -> PerfSolver {
+        self.range = Some(range);
+        self
+    }
+
+    /// Set the fixed environment the target runs in, or `None` to run it in
+    /// the environment of b7 (default: `Some(FixedEnv::default())`)
+    pub fn fixed_env(mut self, env: Option<FixedEnv>) -> PerfSolver {
+        self.env = env;
+        self
+    }
+
+    /// Run inputs in children forked at `point` instead of spawning the
+    /// target for each of them
+    pub fn forkserver(mut self, point: ForkPoint) -> PerfSolver {
+        self.forkserver = Some(Arc::new(ForkServerPool::new(point)));
+        self
+    }
+
+    /// Run memory input in children forked from the target stopped at the
+    /// memory input breakpoint, instead of running the target up to it for
+    /// each input
+    pub fn snapshot(mut self) -> PerfSolver {
+        self.snapshots = Some(Arc::new(SnapshotCache::new()));
+        self
+    }
+
+    /// Count the events of a child forked from a snapshot
+    fn get_snapshot_count(
+        &self,
+        snapshots: &SnapshotCache,
+        data: &InstCountData,
+    ) -> Result<Measurement, SolverError> {
+        debug!("Executing get_snapshot_count:");
+        if self.range.is_some() {
+            return Err(SolverError::new(
+                ArgError,
+                "count ranges and snapshots are mutually exclusive",
+            ));
+        }
+        if self.forkserver.is_some() {
+            return Err(SolverError::new(
+                ArgError,
+                "the forkserver and snapshots are mutually exclusive",
+            ));
+        }
+
+        let path = self.target(&data.path)?;
+        let env = self.env.as_ref();
+        let (group, report) = snapshots.run(&path, env, &data.inp, data.timeout, |pid| {
+            PerfGroup::open(pid.as_raw(), &self.events)
+        })?;
+
+        let counts = group.read(self.multiplex)?;
+        trace!("perf counts: {:?}", counts);
+
+        // Children share the snapshot's output, so their stdout is not measured
+        let measurement = Measurement::new(progress(&counts))
+            .with(EXIT_STATUS, i64::from(report.exit_status))
+            .with(WALL_TIME, report.wall_time.as_micros() as i64);
+        Ok(self.with_counts(measurement, counts))
+    }
+
+    /// Count the events of a child forked by the forkserver
+    fn get_forked_count(
+        &self,
+        pool: &ForkServerPool,
+        data: &InstCountData,
+    ) -> Result<Measurement, SolverError> {
+        debug!("Executing get_forked_count:");
+        if self.range.is_some() {
+            return Err(SolverError::new(
+                ArgError,
+                "count ranges need ptrace, which the forkserver does not use",
+            ));
+        }
+
+        let path = self.target(&data.path)?;
+        let env = self.env.as_ref();
+        let (group, report) = pool.run(&path, env, &data.inp, data.timeout, |pid| {
+            PerfGroup::open(pid.as_raw(), &self.events)
+        })?;
+
+        let counts = group.read(self.multiplex)?;
+        trace!("perf counts: {:?}", counts);
+
+        let measurement = Measurement::new(progress(&counts)).with_exit(
+            report.exit_status,
+            report.wall_time,
+            report.stdout_len,
+        );
+        Ok(self.with_counts(measurement, counts))
+    }
+
+    /// Path to start the binary at `path` with. It has to be found from the
+    /// fixed working directory, if there is one.
+    fn target(&self, path: &Path) -> SolverResult<PathBuf> {
+        match self.env.as_ref().and_then(|env| env.cwd.as_ref()) {
+            Some(_) => Ok(path.canonicalize()?),
+            None => Ok(path.to_path_buf()),
+        }
+    }
+
+    /// Add the count of each event to a measurement
+    fn with_counts(&self, mut measurement: Measurement, counts: Vec<i64>) -> Measurement {
+        for (event, count) in self.events.iter().zip(counts) {
+            measurement.set(&event.name(), count);
+        }
+        measurement
+    }
+}
+
+impl Default for PerfSolver {
+    /// Count retired instructions
+    fn default() -> Self {
+        PerfSolver::new(vec![PerfEvent::default()])
+    }
+}
 
 impl InstCounter for PerfSolver {
     // Handles basic proc spawning and running under perf
     /// runs the processes under ptrace and follow execution with perf
     ///
     /// # Return
-    /// * number of instructions perf says were executed or error
-    fn get_inst_count(&self, data: &InstCountData) -> Result<i64, SolverError> {
+    /// * count of the first event perf measured or error
+    fn get_inst_count(&self, data: &InstCountData) -> Result<Measurement, SolverError> {
         debug!("Executing get_inst_count:");
-        let mut process = Process::new(&data.path)?;
-        if let Some(argv) = data.inp.argv.clone() {
-            for arg in argv {
-                process.arg(OsStr::from_bytes(arg.as_slice()));
-            }
+        if let (Some(snapshots), Some(_)) = (&self.snapshots, &data.inp.mem) {
+            return self.get_snapshot_count(snapshots, data);
         }
-        if let Some(stdin) = data.inp.stdin.clone() {
-            process.stdin_input(stdin);
+        if let Some(pool) = &self.forkserver {
+            return self.get_forked_count(pool, data);
         }
-        if let Some(mem) = data.inp.mem.clone() {
-            process.mem_input(mem);
+
+        let mut process = Process::new(&self.target(&data.path)?)?;
+        if let Some(env) = &self.env {
+            process.fixed_env(env.clone());
         }
+        process.input(&data.inp)?;
 
         let ptrace_mode = if data.drop_ptrace {
             PtraceMode::Drop
@@ -113,14 +499,69 @@ impl InstCounter for PerfSolver {
 
         process.with_ptrace_mode(ptrace_mode);
 
-        let handle = process.spawn();
-        let fd = get_perf_fd(handle.pid().as_raw())?;
-        handle.finish(data.timeout)?;
+        if let Some(range) = self.range {
+            if data.drop_ptrace {
+                return Err(SolverError::new(
+                    ArgError,
+                    "ptrace dropping and count ranges are mutually exclusive",
+                ));
+            }
+            process.count_range(range);
+        }
+
+        let mut handle = process.spawn();
+        let group = PerfGroup::open(handle.pid().as_raw(), &self.events)?;
+        let report = handle.finish_gated(data.timeout, &group)?;
+
+        // Process event counts
+        let counts = group.read(self.multiplex)?;
+        trace!("perf counts: {:?}", counts);
+
+        let measurement = report.measurement(progress(&counts));
+        Ok(self.with_counts(measurement, counts))
+    }
+}
+
+/// The progress of a run is the count of the first event, so that a noisy
+/// event added for its metric does not hide the signal of the first one
+fn progress(counts: &[i64]) -> i64 {
+    counts.first().cloned().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{progress, scale_count, PerfEvent};
 
-        // Process instruction count
-        let ret = perf_get_inst_count(fd);
-        drop(unsafe { File::from_raw_fd(fd) });
+    #[test]
+    fn parse_events() {
+        assert_eq!(
+            PerfEvent::parse_from_arg("instructions").unwrap(),
+            PerfEvent::default()
+        );
+        assert_eq!(
+            PerfEvent::parse_from_arg("r20c4").unwrap(),
+            PerfEvent::Raw(0x20c4)
+        );
+        assert!(PerfEvent::parse_from_arg("page-faults").is_ok());
+        assert!(PerfEvent::parse_from_arg("rxyz").is_err());
+        assert!(PerfEvent::parse_from_arg("not-an-event").is_err());
+        assert_eq!(
+            PerfEvent::parse_list("instructions,branches,r20c4")
+                .unwrap()
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn scale_multiplexed() {
+        assert_eq!(scale_count(100, 10, 10), 100);
+        assert_eq!(scale_count(100, 10, 5), 200);
+    }
 
-        ret
+    #[test]
+    fn progress_of_first_event() {
+        assert_eq!(progress(&[1000, 200, 3]), 1000);
+        assert_eq!(progress(&[]), 0);
     }
 }
@@ -1,23 +1,33 @@
 use crate::binary::Binary;
+use crate::conversation::{Conversation, ConversationThread};
 use crate::errors::Runner::ProcfsError;
 use crate::errors::*;
-use crate::generators::MemInput;
+use crate::generators::{parse_arg_opts, Input, MemInput, RegInput, FILE_PLACEHOLDER};
+use crate::measurement::Measurement;
+use crate::socket::{self, SocketLease, SocketTarget};
+use crate::IS_X86;
 use byteorder::ByteOrder;
 use lazy_static::lazy_static;
 use nix::errno::Errno;
+use nix::fcntl::{fcntl, FcntlArg, FdFlag, OFlag};
 use nix::sys::ptrace;
 use nix::sys::signal::{self, SigSet, SigmaskHow, Signal};
 use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
 use nix::unistd::Pid;
-use std::collections::HashMap;
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
 use std::convert::Into;
-use std::ffi::OsStr;
-use std::io::{Error, Read, Write};
+use std::ffi::{CString, OsStr, OsString};
+use std::fs::{self, OpenOptions};
+use std::io::{Error, ErrorKind, Read, Write};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd, RawFd};
 use std::os::unix::process::CommandExt;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::{Child, Command, Stdio};
 use std::sync::mpsc::{channel, Receiver, Sender};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, Weak};
 use std::time::{Duration, Instant};
 
 const WORD_SIZE: usize = std::mem::size_of::<usize>();
@@ -145,9 +155,8 @@ impl ProcessWaiter {
         let recv;
         process.start().expect("Failed to spawn process!");
         process
-            .write_input()
+            .send_input()
             .expect("Failed writing input to process");
-        process.close_stdin().expect("Failed closing process stdin");
 
         let pid =
             Pid::from_raw(process.child_id().expect("Failed getting process child id") as i32);
@@ -171,6 +180,8 @@ impl ProcessWaiter {
             recv,
             inner: self.inner.clone(),
             proc: process,
+            base_addr: Cell::new(None),
+            stdout: Vec::new(),
         }
     }
 
@@ -313,6 +324,351 @@ impl ProcessWaiter {
     }
 }
 
+lazy_static! {
+    /// Directory the input files of every process are written to
+    static ref INPUT_DIR: Mutex<InputDir> = Mutex::new(InputDir::default());
+}
+
+/// The private directory input files are written to, while a process uses
+/// it, and which slots for file names in it are taken. Every process takes
+/// its own slot, so the paths of its files have the same length in every run.
+#[derive(Debug, Default)]
+struct InputDir {
+    dir: Weak<PrivateDir>,
+    slots: Vec<bool>,
+}
+
+/// Input files of a process, named after its slot in the input directory
+#[derive(Debug)]
+struct InputFiles {
+    dir: Arc<PrivateDir>,
+    slot: usize,
+    /// Names of the files, with their contents
+    files: Vec<(String, Vec<u8>)>,
+}
+
+impl InputFiles {
+    /// Take the first free slot in the input directory, creating the
+    /// directory if no other process uses it
+    fn acquire() -> SolverResult<InputFiles> {
+        let mut shared = INPUT_DIR.lock().unwrap();
+        let dir = match shared.dir.upgrade() {
+            Some(dir) => dir,
+            None => {
+                let dir = Arc::new(PrivateDir::new("b7-files")?);
+                shared.dir = Arc::downgrade(&dir);
+                dir
+            }
+        };
+        let slot = match shared.slots.iter().position(|taken| !taken) {
+            Some(slot) => slot,
+            None => {
+                shared.slots.push(false);
+                shared.slots.len() - 1
+            }
+        };
+        shared.slots[slot] = true;
+
+        Ok(InputFiles {
+            dir,
+            slot,
+            files: Vec::new(),
+        })
+    }
+
+    /// Add a file with `contents`, returning its path
+    fn add(&mut self, contents: Vec<u8>) -> PathBuf {
+        let name = format!("{:04}-{:02}", self.slot, self.files.len());
+        let path = self.dir.path().join(&name);
+        self.files.push((name, contents));
+        path
+    }
+
+    /// Create the files, failing if any of them exists
+    fn write(&self) -> SolverResult<()> {
+        for (name, contents) in &self.files {
+            self.dir.create(name, contents, 0o600)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for InputFiles {
+    fn drop(&mut self) {
+        for (name, _) in &self.files {
+            let _ = fs::remove_file(self.dir.path().join(name));
+        }
+        INPUT_DIR.lock().unwrap().slots[self.slot] = false;
+    }
+}
+
+/// A directory only the user running b7 can access, removed with everything
+/// in it on drop. Files handed to targets are created in one instead of at
+/// predictable paths in the shared temporary directory, where another user
+/// could plant them first.
+#[derive(Debug)]
+pub struct PrivateDir {
+    path: PathBuf,
+}
+
+impl PrivateDir {
+    /// Create a directory with mode 0700 in the temporary directory, named
+    /// `prefix` followed by a random suffix
+    pub fn new(prefix: &str) -> SolverResult<PrivateDir> {
+        debug!("Executing PrivateDir::new:");
+        let template = std::env::temp_dir().join(format!("{}-XXXXXX", prefix));
+        let template = CString::new(template.into_os_string().into_vec())
+            .map_err(|_| SolverError::new(Runner::IoError, "Invalid temporary directory"))?;
+        let template = template.into_raw();
+        let created = unsafe { libc::mkdtemp(template) };
+        let template = unsafe { CString::from_raw(template) };
+        if created.is_null() {
+            return Err(Error::last_os_error().into());
+        }
+
+        Ok(PrivateDir {
+            path: PathBuf::from(OsString::from_vec(template.into_bytes())),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Create the file `name` in the directory with `contents` and `mode`,
+    /// failing if it already exists
+    pub fn create(&self, name: &str, contents: &[u8], mode: u32) -> SolverResult<PathBuf> {
+        let path = self.path.join(name);
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(mode)
+            .open(&path)?;
+        file.write_all(contents)?;
+        Ok(path)
+    }
+}
+
+impl Drop for PrivateDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// A library for `LD_PRELOAD`, written to a `PrivateDir` the first time its
+/// path is needed
+#[derive(Debug)]
+pub struct PreloadLib {
+    prefix: &'static str,
+    name: &'static str,
+    contents: &'static [u8],
+    /// Private directory the library was written to, and its path
+    written: Mutex<Option<(PrivateDir, PathBuf)>>,
+}
+
+impl PreloadLib {
+    /// A library `name` with `contents`, to be written to a private directory
+    /// named after `prefix`
+    pub fn new(prefix: &'static str, name: &'static str, contents: &'static [u8]) -> PreloadLib {
+        PreloadLib {
+            prefix,
+            name,
+            contents,
+            written: Mutex::new(None),
+        }
+    }
+
+    /// Get the path of the library, writing it out the first time
+    pub fn path(&self) -> SolverResult<PathBuf> {
+        debug!("Executing PreloadLib::path:");
+        let mut written = self.written.lock().unwrap();
+        if let Some((_, path)) = &*written {
+            return Ok(path.clone());
+        }
+
+        let dir = PrivateDir::new(self.prefix)?;
+        let path = dir.create(self.name, self.contents, 0o500)?;
+        *written = Some((dir, path.clone()));
+
+        Ok(path)
+    }
+}
+
+/// `personality(2)` flag that disables address space randomization
+const ADDR_NO_RANDOMIZE: libc::c_ulong = 0x0040000;
+
+/// A fixed environment to run the target in, so that runs of the same input
+/// count the same. The default disables ASLR and runs with a minimal
+/// environment and a fixed argv[0].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixedEnv {
+    /// Disable address space randomization
+    pub no_aslr: bool,
+    /// Environment variables, replacing the ones b7 runs with
+    pub vars: Vec<(String, String)>,
+    /// argv[0], instead of the path of the binary
+    pub argv0: String,
+    /// Working directory, instead of the one b7 runs in
+    pub cwd: Option<PathBuf>,
+}
+
+impl Default for FixedEnv {
+    fn default() -> Self {
+        FixedEnv {
+            no_aslr: true,
+            vars: vec![
+                ("PATH".to_string(), "/usr/bin:/bin".to_string()),
+                ("LANG".to_string(), "C".to_string()),
+            ],
+            argv0: "target".to_string(),
+            cwd: None,
+        }
+    }
+}
+
+impl FixedEnv {
+    /// Set up `cmd` to run in the fixed environment
+    pub(crate) fn apply(&self, cmd: &mut Command) {
+        cmd.env_clear();
+        cmd.envs(self.vars.iter().cloned());
+        cmd.arg0(&self.argv0);
+        if let Some(cwd) = &self.cwd {
+            cmd.current_dir(cwd);
+        }
+
+        if self.no_aslr {
+            unsafe {
+                cmd.pre_exec(|| {
+                    // Keep the other flags of the current persona
+                    let persona =
+                        libc::syscall(libc::SYS_personality, 0xffff_ffff as libc::c_ulong);
+                    if persona == -1
+                        || libc::syscall(
+                            libc::SYS_personality,
+                            persona as libc::c_ulong | ADDR_NO_RANDOMIZE,
+                        ) == -1
+                    {
+                        return Err(Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+        }
+    }
+}
+
+/// Address range to count in. For PIE binaries, the addresses are relative to
+/// the executable base. Otherwise, the addresses are absolute.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CountRange {
+    /// Address to start counting at. If `None`, count from the beginning of
+    /// execution.
+    pub start: Option<usize>,
+    /// Address to stop counting at. If `None`, count until the process exits.
+    pub stop: Option<usize>,
+}
+
+impl CountRange {
+    /// Parse a count range from an argument of the format:
+    ///
+    /// ``` text
+    /// start=XXX,stop=YYY
+    /// ```
+    pub fn parse_from_arg(arg: &str) -> SolverResult<Self> {
+        debug!("Executing parse_from_arg:");
+        let opts = parse_arg_opts(arg, "Invalid count range usage")?;
+
+        // Parse addresses to integers
+        let parse_addr = |key: &str| match opts.get(key) {
+            Some(addr) => usize::from_str_radix(addr, 0x10)
+                .map(Some)
+                .map_err(|_| SolverError::new(Runner::ArgError, "Invalid count range address")),
+            None => Ok(None),
+        };
+        let start = parse_addr("start")?;
+        let stop = parse_addr("stop")?;
+
+        if start.is_none() && stop.is_none() {
+            return Err(SolverError::new(
+                Runner::ArgError,
+                "Count range has no start or stop address",
+            ));
+        }
+
+        if !IS_X86 {
+            return Err(SolverError::new(
+                Runner::ArgError,
+                "Breakpoints only work on x86",
+            ));
+        }
+
+        Ok(Self { start, stop })
+    }
+}
+
+/// A counter that is switched on and off as execution enters and leaves the
+/// counted address range
+pub trait CountGate {
+    /// Start counting
+    fn enable(&self) -> SolverResult<()>;
+    /// Stop counting
+    fn disable(&self) -> SolverResult<()>;
+}
+
+/// Which coverage of basic blocks to measure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Coverage {
+    /// Unique basic blocks reached
+    Blocks,
+    /// Unique edges between consecutively reached basic blocks
+    Edges,
+}
+
+/// Basic blocks to measure coverage of
+#[derive(Debug, Clone)]
+struct CoverageConfig {
+    mode: Coverage,
+    /// Basic block addresses. For PIE binaries, these are relative to the
+    /// executable base.
+    blocks: Arc<Vec<usize>>,
+}
+
+/// What to do when a breakpoint is reached
+#[derive(Debug, Clone)]
+enum BreakpointAction {
+    /// Write a memory input into the process
+    MemInput(MemInput),
+    /// Set a register of the process
+    RegInput(RegInput),
+    /// Enter the counted address range
+    StartCounting,
+    /// Leave the counted address range
+    StopCounting,
+    /// Record reaching a basic block for the first time
+    Block,
+    /// Record the edge from the last reached basic block
+    Edge,
+    /// Count reaching a watched address
+    Hit,
+}
+
+impl BreakpointAction {
+    /// Whether the breakpoint has to be re-armed after this action runs.
+    /// Memory and register input is only written once and a block only has
+    /// to be reached once, but the other actions run every time.
+    fn is_persistent(&self) -> bool {
+        match self {
+            BreakpointAction::MemInput(_)
+            | BreakpointAction::RegInput(_)
+            | BreakpointAction::Block => false,
+            BreakpointAction::StartCounting
+            | BreakpointAction::StopCounting
+            | BreakpointAction::Edge
+            | BreakpointAction::Hit => true,
+        }
+    }
+}
+
 /// Information associated with a breakpoint
 #[derive(Debug)]
 struct BreakpointInfo {
@@ -321,8 +677,8 @@ struct BreakpointInfo {
     /// saved here so they can be restored when the breakpoint is reached and
     /// removed.
     saved_bytes: usize,
-    /// Memory input associated with breakpoint
-    mem_input: MemInput,
+    /// Actions to run when the breakpoint is reached
+    actions: Vec<BreakpointAction>,
 }
 
 #[derive(Debug)]
@@ -332,12 +688,60 @@ pub struct Process {
     child: Option<Child>,
     stdin_input: Vec<u8>,
     mem_input: Vec<MemInput>,
+    reg_input: Vec<RegInput>,
+    count_range: Option<CountRange>,
+    coverage: Option<CoverageConfig>,
+    watched: Vec<usize>,
+    inherited_fds: Vec<RawFd>,
+    discard_output: bool,
+    env_vars: Vec<(OsString, OsString)>,
+    fixed_env: Option<FixedEnv>,
+    /// Input files to write before the process starts
+    files: Option<InputFiles>,
+    /// Where to send input to once the process listens, instead of stdin
+    socket: Option<(SocketTarget, Vec<u8>)>,
+    /// Conversation to hold on stdin and stdout, with the input to send in it
+    conversation: Option<(Conversation, Vec<u8>)>,
+    /// Thread holding the conversation, which returns the stdout it read
+    conversation_thread: Option<ConversationThread>,
     breakpoints: BreakpointMap,
     ptrace_mode: PtraceMode,
+    resume_mode: ResumeMode,
+}
+
+/// What was observed while a process ran, returned by
+/// `ProcessHandle::finish()`
+#[derive(Debug, Clone)]
+pub struct TraceReport {
+    /// Process ID
+    pub pid: Pid,
+    /// Instructions single stepped inside the counted address range
+    pub steps: u64,
+    /// Unique basic blocks reached inside the counted address range
+    pub blocks: usize,
+    /// Unique edges between basic blocks inside the counted address range
+    pub edges: usize,
+    /// Syscalls made inside the counted address range, by syscall number
+    pub syscalls: HashMap<u64, u64>,
+    /// Times each watched address was reached inside the counted address range
+    pub hits: HashMap<usize, u64>,
+    /// Exit code, or 128 plus the signal that killed the process
+    pub exit_status: i32,
+    /// Time from starting to wait for the process until it exited
+    pub wall_time: Duration,
+    /// Bytes the process wrote to stdout
+    pub stdout_len: usize,
+}
+
+impl TraceReport {
+    /// Measurement of the run with `progress` and the exit metrics
+    pub fn measurement(&self, progress: i64) -> Measurement {
+        Measurement::new(progress).with_exit(self.exit_status, self.wall_time, self.stdout_len)
+    }
 }
 
 /// State for function `ProcessHandle::finish()`
-struct ProcessFinishState {
+struct ProcessFinishState<'a> {
     /// Process timeout
     timeout: Duration,
 
@@ -352,32 +756,144 @@ struct ProcessFinishState {
 
     /// Placed breakpoints
     breakpoints: BreakpointMap,
+
+    /// Whether execution is inside the counted address range
+    counting: bool,
+
+    /// Absolute address of a breakpoint to re-insert once the process has
+    /// single stepped over it
+    rearm: Option<usize>,
+
+    /// Counter to switch on and off at the counted address range
+    gate: Option<&'a dyn CountGate>,
+
+    /// Whether the process was single stepped when it was last resumed
+    stepped: bool,
+
+    /// Instructions single stepped inside the counted address range
+    steps: u64,
+
+    /// Basic blocks reached inside the counted address range
+    blocks: HashSet<usize>,
+
+    /// Edges between basic blocks reached inside the counted address range
+    edges: HashSet<(usize, usize)>,
+
+    /// Last basic block reached
+    last_block: Option<usize>,
+
+    /// Whether the process is stopped inside a syscall
+    in_syscall: bool,
+
+    /// Syscalls made inside the counted address range, by syscall number
+    syscalls: HashMap<u64, u64>,
+
+    /// Times each watched address was reached inside the counted address range
+    hits: HashMap<usize, u64>,
 }
 
-impl ProcessFinishState {
+impl<'a> ProcessFinishState<'a> {
     /// Constructor with default values
-    fn new(timeout: Duration) -> Self {
+    fn new(timeout: Duration, gate: Option<&'a dyn CountGate>) -> Self {
         Self {
             timeout,
             start: Instant::now(),
             time_left: timeout,
             init_ptrace: false,
             breakpoints: BreakpointMap::new(),
+            counting: true,
+            rearm: None,
+            gate,
+            stepped: false,
+            steps: 0,
+            blocks: HashSet::new(),
+            edges: HashSet::new(),
+            last_block: None,
+            in_syscall: false,
+            syscalls: HashMap::new(),
+            hits: HashMap::new(),
+        }
+    }
+
+    /// Record reaching a watched address
+    fn hit(&mut self, addr: usize) {
+        if self.counting {
+            *self.hits.entry(addr).or_insert(0) += 1;
+        }
+    }
+
+    /// Record reaching a basic block
+    fn reach_block(&mut self, addr: usize) {
+        if !self.counting {
+            return;
+        }
+
+        self.blocks.insert(addr);
+        if let Some(last) = self.last_block {
+            self.edges.insert((last, addr));
+        }
+        self.last_block = Some(addr);
+    }
+
+    /// Enter or leave the counted address range
+    fn set_counting(&mut self, counting: bool) -> SolverResult<()> {
+        self.counting = counting;
+        match self.gate {
+            Some(gate) if counting => gate.enable(),
+            Some(gate) => gate.disable(),
+            None => Ok(()),
         }
     }
 }
 
+/// Get the register named `name`, one of `generators::REGISTERS`
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn register<'a>(regs: &'a mut libc::user_regs_struct, name: &str) -> SolverResult<&'a mut u64> {
+    Ok(match name {
+        "rax" => &mut regs.rax,
+        "rbx" => &mut regs.rbx,
+        "rcx" => &mut regs.rcx,
+        "rdx" => &mut regs.rdx,
+        "rsi" => &mut regs.rsi,
+        "rdi" => &mut regs.rdi,
+        "rbp" => &mut regs.rbp,
+        "rsp" => &mut regs.rsp,
+        "r8" => &mut regs.r8,
+        "r9" => &mut regs.r9,
+        "r10" => &mut regs.r10,
+        "r11" => &mut regs.r11,
+        "r12" => &mut regs.r12,
+        "r13" => &mut regs.r13,
+        "r14" => &mut regs.r14,
+        "r15" => &mut regs.r15,
+        _ => {
+            return Err(SolverError::new(
+                Runner::ArgError,
+                &format!("Unknown register {}", name),
+            ))
+        }
+    })
+}
+
 pub struct ProcessHandle {
     pid: Pid,
     inner: Arc<Mutex<ProcessWaiterInner>>,
     recv: Receiver<WaitData>,
     proc: Process,
+    /// Cached executable base address
+    base_addr: Cell<Option<usize>>,
+    /// Stdout that was read when the process exited
+    stdout: Vec<u8>,
 }
 
 impl ProcessHandle {
     /// Get the process's base address from /proc/<pid>/maps
     fn get_base_addr(&self) -> SolverResult<usize> {
         debug!("Executing get_base_addr:");
+        if let Some(base_addr) = self.base_addr.get() {
+            return Ok(base_addr);
+        }
+
         let proc = procfs::Process::new(self.pid.as_raw())?;
         let maps = proc.maps()?;
         let exe_path = proc.exe()?;
@@ -394,7 +910,9 @@ impl ProcessHandle {
                 )
             })?;
 
-        Ok(base_map.address.0 as usize)
+        let base_addr = base_map.address.0 as usize;
+        self.base_addr.set(Some(base_addr));
+        Ok(base_addr)
     }
 
     /// If the binary is PIE, convert from an address relative to the executable
@@ -428,6 +946,12 @@ impl ProcessHandle {
     /// Write a memory input range to the process
     /// NOTE: This assumes `self.proc.ptrace` is `true`
     fn write_mem_input(&self, mem: &MemInput) -> SolverResult<()> {
+        self.write_mem_input_to(self.pid, mem)
+    }
+
+    /// Write a memory input range to `pid`, which is the process or a fork of
+    /// it
+    fn write_mem_input_to(&self, pid: Pid, mem: &MemInput) -> SolverResult<()> {
         debug!("Executing write_mem_input:");
         for (nth_word, word) in mem.bytes.chunks(WORD_SIZE).enumerate() {
             // Use relative address if binary is PIE
@@ -447,16 +971,15 @@ impl ProcessHandle {
             let word = word as ptrace::AddressType;
 
             // Do the write
-            ptrace::write(self.pid, addr, word)?;
+            ptrace::write(pid, addr, word)?;
         }
 
         Ok(())
     }
 
-    /// Add a breakpoint to the running process
-    fn add_breakpoint(&self, addr: usize, mem_input: &MemInput) -> SolverResult<BreakpointInfo> {
-        let addr = self.abs_addr(addr)?;
-
+    /// Write the breakpoint opcode at an absolute address, returning the
+    /// bytes it replaced
+    fn write_int3(&self, addr: usize) -> SolverResult<usize> {
         // Save bytes so the breakpoint can be removed later
         let bytes = ptrace::read(self.pid, addr as ptrace::AddressType)? as usize;
 
@@ -470,10 +993,47 @@ impl ProcessHandle {
             bp_bytes as ptrace::AddressType,
         )?;
 
-        Ok(BreakpointInfo {
-            saved_bytes: bytes,
-            mem_input: mem_input.clone(),
-        })
+        Ok(bytes)
+    }
+
+    /// Restore the byte a breakpoint replaced at an absolute address. Only the
+    /// opcode byte is restored, so breakpoints in the same word are kept.
+    fn remove_int3(&self, addr: usize, saved_bytes: usize) -> SolverResult<()> {
+        let bytes = ptrace::read(self.pid, addr as ptrace::AddressType)? as usize;
+        let bytes = bytes & (std::usize::MAX ^ 0xff) | saved_bytes & 0xff;
+
+        ptrace::write(
+            self.pid,
+            addr as ptrace::AddressType,
+            bytes as ptrace::AddressType,
+        )?;
+
+        Ok(())
+    }
+
+    /// Add a breakpoint action to the running process, placing a breakpoint
+    /// if there is none at the address yet
+    fn add_breakpoint(
+        &self,
+        addr: usize,
+        action: BreakpointAction,
+        breakpoints: &mut BreakpointMap,
+    ) -> SolverResult<()> {
+        if let Some(bp_info) = breakpoints.get_mut(&addr) {
+            bp_info.actions.push(action);
+            return Ok(());
+        }
+
+        let saved_bytes = self.write_int3(self.abs_addr(addr)?)?;
+        breakpoints.insert(
+            addr,
+            BreakpointInfo {
+                saved_bytes,
+                actions: vec![action],
+            },
+        );
+
+        Ok(())
     }
 
     /// Initialize memory input
@@ -486,8 +1046,11 @@ impl ProcessHandle {
         for mem in &self.proc.mem_input {
             match mem.breakpoint {
                 Some(bp_addr) => {
-                    let bp_info = self.add_breakpoint(bp_addr, mem)?;
-                    breakpoints.insert(bp_addr, bp_info);
+                    self.add_breakpoint(
+                        bp_addr,
+                        BreakpointAction::MemInput(mem.clone()),
+                        breakpoints,
+                    )?;
                 }
                 None => self.write_mem_input(mem)?,
             }
@@ -496,9 +1059,89 @@ impl ProcessHandle {
         Ok(())
     }
 
-    /// Handle potentially reached breakpoint
+    /// Initialize register input
+    ///
+    /// - Set up breakpoints
+    ///
+    /// NOTE: This assumes `self.proc.ptrace` is `true`
+    fn init_reg_input(&self, breakpoints: &mut BreakpointMap) -> SolverResult<()> {
+        for reg in &self.proc.reg_input {
+            self.add_breakpoint(
+                reg.breakpoint,
+                BreakpointAction::RegInput(reg.clone()),
+                breakpoints,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Initialize the counted address range
+    ///
+    /// - Set up breakpoints at the start and stop addresses
+    /// - Stop counting until the start address is reached
+    ///
+    /// NOTE: This assumes `self.proc.ptrace` is `true`
+    fn init_count_range(&self, state: &mut ProcessFinishState) -> SolverResult<()> {
+        let range = match self.proc.count_range {
+            Some(range) => range,
+            None => return Ok(()),
+        };
+
+        if let Some(start) = range.start {
+            self.add_breakpoint(
+                start,
+                BreakpointAction::StartCounting,
+                &mut state.breakpoints,
+            )?;
+            state.set_counting(false)?;
+        }
+
+        if let Some(stop) = range.stop {
+            self.add_breakpoint(stop, BreakpointAction::StopCounting, &mut state.breakpoints)?;
+        }
+
+        Ok(())
+    }
+
+    /// Initialize basic block coverage
+    ///
+    /// - Set up a breakpoint at every basic block
+    ///
+    /// NOTE: This assumes `self.proc.ptrace` is `true`
+    fn init_coverage(&self, breakpoints: &mut BreakpointMap) -> SolverResult<()> {
+        let coverage = match &self.proc.coverage {
+            Some(coverage) => coverage,
+            None => return Ok(()),
+        };
+
+        let action = match coverage.mode {
+            Coverage::Blocks => BreakpointAction::Block,
+            Coverage::Edges => BreakpointAction::Edge,
+        };
+        for &block in coverage.blocks.iter() {
+            self.add_breakpoint(block, action.clone(), breakpoints)?;
+        }
+
+        Ok(())
+    }
+
+    /// Initialize watched addresses
+    ///
+    /// - Set up a breakpoint at every watched address
+    ///
+    /// NOTE: This assumes `self.proc.ptrace` is `true`
+    fn init_watched(&self, breakpoints: &mut BreakpointMap) -> SolverResult<()> {
+        for &addr in &self.proc.watched {
+            self.add_breakpoint(addr, BreakpointAction::Hit, breakpoints)?;
+        }
+
+        Ok(())
+    }
+
+    /// Handle potentially reached breakpoint, returning whether one was reached
     #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
-    fn handle_reached_breakpoint(&self, breakpoints: &mut BreakpointMap) -> SolverResult<()> {
+    fn handle_reached_breakpoint(&self, state: &mut ProcessFinishState) -> SolverResult<bool> {
         // Check if the instruction pointer is at a breakpoint
         let mut regs = ptrace::getregs(self.pid)?;
 
@@ -508,34 +1151,103 @@ impl ProcessHandle {
 
         let rel_ip = self.rel_addr(regs.rip as usize)?;
 
-        if let Some(bp_info) = breakpoints.get(&rel_ip) {
-            self.write_mem_input(&bp_info.mem_input)?;
+        let mut bp_info = match state.breakpoints.remove(&rel_ip) {
+            Some(bp_info) => bp_info,
+            None => return Ok(false),
+        };
 
-            // Remove breakpoint
-            ptrace::write(
-                self.pid,
-                regs.rip as ptrace::AddressType,
-                bp_info.saved_bytes as ptrace::AddressType,
-            )?;
+        for action in &bp_info.actions {
+            match action {
+                BreakpointAction::MemInput(mem) => self.write_mem_input(mem)?,
+                BreakpointAction::RegInput(reg) => {
+                    let value = register(&mut regs, &reg.reg)?;
+                    *value = reg.apply(*value);
+                }
+                BreakpointAction::StartCounting => state.set_counting(true)?,
+                BreakpointAction::StopCounting => state.set_counting(false)?,
+                BreakpointAction::Block | BreakpointAction::Edge => state.reach_block(rel_ip),
+                BreakpointAction::Hit => state.hit(rel_ip),
+            }
+        }
 
-            // Decrement instruction pointer
-            ptrace::setregs(self.pid, regs)?;
+        // Remove breakpoint
+        self.remove_int3(regs.rip as usize, bp_info.saved_bytes)?;
 
-            breakpoints.remove(&rel_ip);
+        // Decrement instruction pointer
+        ptrace::setregs(self.pid, regs)?;
+
+        // Breakpoints that are reached more than once are put back after the
+        // original instruction is stepped over
+        bp_info.actions.retain(BreakpointAction::is_persistent);
+        if !bp_info.actions.is_empty() {
+            state.rearm = Some(regs.rip as usize);
+            state.breakpoints.insert(rel_ip, bp_info);
         }
 
-        Ok(())
+        Ok(true)
     }
 
-    /// Handle potentially reached breakpoint
+    /// Handle potentially reached breakpoint, returning whether one was reached
     #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
-    fn handle_reached_breakpoint(&self, _breakpoints: &mut BreakpointMap) -> SolverResult<()> {
+    fn handle_reached_breakpoint(&self, state: &mut ProcessFinishState) -> SolverResult<bool> {
+        if state.breakpoints.is_empty() {
+            return Ok(false);
+        }
+
         Err(SolverError::new(
             Runner::ArgError,
             "Breakpoints only supported on x86",
         ))
     }
 
+    /// Get the number of the syscall the process is stopped in
+    #[cfg(target_arch = "x86_64")]
+    fn syscall_number(&self) -> SolverResult<u64> {
+        Ok(ptrace::getregs(self.pid)?.orig_rax)
+    }
+
+    /// Get the number of the syscall the process is stopped in
+    #[cfg(not(target_arch = "x86_64"))]
+    fn syscall_number(&self) -> SolverResult<u64> {
+        Err(SolverError::new(
+            Runner::ArgError,
+            "Syscall tracing only supported on x86_64",
+        ))
+    }
+
+    /// Resume the process until the next syscall entry or exit. Unlike
+    /// `ptrace::syscall`, this can deliver a signal.
+    fn resume_syscall(&self, signal: Option<Signal>) -> nix::Result<()> {
+        let signal = signal.map(|s| s as libc::c_int).unwrap_or(0);
+        let res = unsafe {
+            libc::ptrace(
+                libc::PTRACE_SYSCALL,
+                self.pid.as_raw(),
+                std::ptr::null_mut::<libc::c_void>(),
+                signal as *mut libc::c_void,
+            )
+        };
+        Errno::result(res).map(drop)
+    }
+
+    /// Handle a syscall entry or exit stop
+    fn handle_syscall_stop(&self, state: &mut ProcessFinishState) -> SolverResult<()> {
+        // Stops alternate between entering and leaving a syscall
+        state.in_syscall = !state.in_syscall;
+        if state.in_syscall && state.counting {
+            let nr = self.syscall_number()?;
+            *state.syscalls.entry(nr).or_insert(0) += 1;
+        }
+
+        self.handle_stop(None, state)
+    }
+
+    /// Whether the last SIGTRAP came from an int3 rather than a single step
+    fn trapped_by_int3(&self) -> SolverResult<bool> {
+        let info = ptrace::getsiginfo(self.pid)?;
+        Ok(info.si_code == libc::SI_KERNEL || info.si_code == libc::TRAP_BRKPT)
+    }
+
     /// Handle a stop while the process is being ptrace'd
     fn handle_ptrace_stop(
         &self,
@@ -545,10 +1257,35 @@ impl ProcessHandle {
         // Initialize breakpoints and memory regions if first stop
         if !state.init_ptrace {
             self.init_mem_input(&mut state.breakpoints)?;
+            self.init_reg_input(&mut state.breakpoints)?;
+            self.init_count_range(state)?;
+            self.init_coverage(&mut state.breakpoints)?;
+            self.init_watched(&mut state.breakpoints)?;
+            if self.proc.resume_mode == ResumeMode::Syscall {
+                // Report syscall stops as `WaitStatus::PtraceSyscall`
+                ptrace::setoptions(self.pid, ptrace::Options::PTRACE_O_TRACESYSGOOD)?;
+            }
             state.init_ptrace = true;
         }
 
-        self.handle_reached_breakpoint(&mut state.breakpoints)?;
+        let trapped = signal == Some(Signal::SIGTRAP);
+        let reached_breakpoint = match state.rearm.take() {
+            // Stepped over a breakpoint that has to stay, so put it back
+            Some(addr) => {
+                self.write_int3(addr)?;
+                false
+            }
+            // A single step that lands right after a breakpoint is no hit
+            None if trapped && (!state.stepped || self.trapped_by_int3()?) => {
+                self.handle_reached_breakpoint(state)?
+            }
+            None => false,
+        };
+
+        // Count the instruction that was just single stepped
+        if state.stepped && trapped && !reached_breakpoint && state.counting {
+            state.steps += 1;
+        }
 
         // SIGTRAP should not be forwarded to the process, since breakpoints and
         // stopping on execve will crash the process and it will be bad
@@ -558,13 +1295,18 @@ impl ProcessHandle {
             signal
         };
 
-        // Continue process
-        ptrace::cont(self.pid, signal).unwrap_or_else(|e| {
-            panic!(
-                "Failed to call ptrace::cont for pid {:?}: {:?}",
-                self.pid, e
-            )
-        });
+        // Continue process, single stepping if a breakpoint has to be re-armed
+        // or instructions are being counted by stepping
+        state.stepped = state.rearm.is_some()
+            || (self.proc.resume_mode == ResumeMode::SingleStep && state.counting);
+        let resumed = if state.stepped {
+            ptrace::step(self.pid, signal)
+        } else if self.proc.resume_mode == ResumeMode::Syscall {
+            self.resume_syscall(signal)
+        } else {
+            ptrace::cont(self.pid, signal)
+        };
+        resumed.unwrap_or_else(|e| panic!("Failed to resume pid {:?}: {:?}", self.pid, e));
 
         Ok(())
     }
@@ -598,25 +1340,88 @@ impl ProcessHandle {
     }
 
     /// run process until it exits or times out
-    pub fn finish(&self, timeout: Duration) -> SolverResult<Pid> {
+    pub fn finish(&mut self, timeout: Duration) -> SolverResult<TraceReport> {
         debug!("Executing finish:");
-        let mut state = ProcessFinishState::new(timeout);
+        self.run_to_exit(timeout, None)
+    }
+
+    /// run process until it exits or times out, switching `gate` on and off
+    /// as execution enters and leaves the counted address range
+    pub fn finish_gated(
+        &mut self,
+        timeout: Duration,
+        gate: &dyn CountGate,
+    ) -> SolverResult<TraceReport> {
+        debug!("Executing finish_gated:");
+        self.run_to_exit(timeout, Some(gate))
+    }
+
+    fn run_to_exit(
+        &mut self,
+        timeout: Duration,
+        gate: Option<&dyn CountGate>,
+    ) -> SolverResult<TraceReport> {
+        let report = self.trace_to_exit(timeout, gate);
+        // Input files are only needed while the process runs
+        self.proc.remove_files();
+        report
+    }
+
+    fn trace_to_exit(
+        &mut self,
+        timeout: Duration,
+        gate: Option<&dyn CountGate>,
+    ) -> SolverResult<TraceReport> {
+        let mut state = ProcessFinishState::new(timeout, gate);
 
         loop {
             let data = self
                 .recv
                 .recv_timeout(state.time_left)
                 .expect("Receieve error!");
-            match data.status {
-                WaitStatus::Exited(_, _) => {
-                    // Remove process data from the map now that it has exited
-                    self.inner.lock().unwrap().proc_chans.remove(&data.pid);
-                    return Ok(data.pid);
+            let exit_status = match data.status {
+                WaitStatus::Exited(_, code) => code,
+                WaitStatus::Signaled(_, signal, _) => 128 + signal as i32,
+                WaitStatus::PtraceSyscall(_) => {
+                    self.handle_syscall_stop(&mut state)?;
+                    continue;
+                }
+                WaitStatus::Stopped(_, signal) => {
+                    self.handle_stop(Some(signal), &mut state)?;
+                    continue;
+                }
+                _ => {
+                    self.handle_stop(None, &mut state)?;
+                    continue;
                 }
-                WaitStatus::Stopped(_, signal) => self.handle_stop(Some(signal), &mut state)?,
-                _ => self.handle_stop(None, &mut state)?,
+            };
+
+            // Remove process data from the map now that it has exited
+            self.inner.lock().unwrap().proc_chans.remove(&data.pid);
+            return Ok(TraceReport {
+                pid: data.pid,
+                steps: state.steps,
+                blocks: state.blocks.len(),
+                edges: state.edges.len(),
+                syscalls: state.syscalls,
+                hits: state.hits,
+                exit_status,
+                wall_time: state.start.elapsed(),
+                stdout_len: self.stdout_len(),
+            });
+        }
+    }
+
+    /// read the process's stdout into `self.stdout` and return its length,
+    /// without blocking if something else still has it open
+    fn stdout_len(&mut self) -> usize {
+        self.end_conversation();
+        if let Some(stdout) = self.proc.child.as_mut().and_then(|c| c.stdout.as_mut()) {
+            if fcntl(stdout.as_raw_fd(), FcntlArg::F_SETFL(OFlag::O_NONBLOCK)).is_ok() {
+                let _ = stdout.read_to_end(&mut self.stdout);
             }
         }
+        self.stdout.len()
     }
 
     pub fn pid(&self) -> Pid {
@@ -632,12 +1437,279 @@ impl ProcessHandle {
                 "child process not running",
             ));
         }
+        // Include what was already read by `finish()` or the conversation
+        self.end_conversation();
+        let buffered = self.stdout.len();
+        buf.append(&mut self.stdout);
+
         let child = self.proc.child.as_mut().unwrap();
         match child.stdout.as_mut() {
-            Some(stdout) => stdout.read_to_end(buf).map_err(Into::into),
+            Some(stdout) => match stdout.read_to_end(buf) {
+                Ok(read) => Ok(buffered + read),
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => Ok(buffered),
+                Err(e) => Err(e.into()),
+            },
+            None if self.proc.conversation.is_some() => Ok(buffered),
             None => Err(Error::last_os_error().into()),
         }
     }
+
+    /// wait until the conversation thread read the stdout the exited process
+    /// left, and keep it
+    fn end_conversation(&mut self) {
+        if let Some(thread) = self.proc.conversation_thread.take() {
+            self.stdout.append(&mut thread.finish());
+        }
+    }
+}
+
+/// Number of the clone syscall
+#[cfg(target_arch = "x86_64")]
+const SYS_CLONE: u64 = 56;
+
+/// Flags of the clone syscall that forks a snapshot. With `CLONE_PARENT` the
+/// children are siblings of the snapshot, so b7 reaps them.
+#[cfg(target_arch = "x86_64")]
+const SNAPSHOT_CLONE_FLAGS: u64 = (libc::CLONE_PARENT | libc::SIGCHLD) as u64;
+
+/// Wait for the next stop of a snapshot or one of its children, failing if it
+/// exited or `deadline` passed
+#[cfg(target_arch = "x86_64")]
+fn snapshot_stop(recv: &Receiver<WaitData>, deadline: Instant) -> SolverResult<WaitStatus> {
+    let now = Instant::now();
+    if now >= deadline {
+        return Err(SolverError::new(Runner::Timeout, "snapshot timeout"));
+    }
+
+    match recv.recv_timeout(deadline - now) {
+        Ok(WaitData {
+            status: WaitStatus::Exited(..),
+            ..
+        })
+        | Ok(WaitData {
+            status: WaitStatus::Signaled(..),
+            ..
+        }) => Err(SolverError::new(
+            Runner::RunnerError,
+            "process exited before reaching the snapshot breakpoint",
+        )),
+        Ok(data) => Ok(data.status),
+        Err(_) => Err(SolverError::new(Runner::Timeout, "snapshot timeout")),
+    }
+}
+
+// Snapshots: a process stopped at a breakpoint that is forked for every input
+impl ProcessHandle {
+    /// Run the process until execution reaches `breakpoint`, or until it
+    /// exec'd if there is none, and leave it stopped there as a snapshot to
+    /// fork children from with `fork_snapshot()`
+    #[cfg(target_arch = "x86_64")]
+    pub fn run_to_snapshot(
+        &self,
+        breakpoint: Option<usize>,
+        timeout: Duration,
+    ) -> SolverResult<()> {
+        debug!("Executing run_to_snapshot:");
+        let deadline = Instant::now() + timeout;
+
+        // The first stop is right after exec
+        snapshot_stop(&self.recv, deadline)?;
+        if let Some(breakpoint) = breakpoint {
+            let addr = self.abs_addr(breakpoint)?;
+            let saved_bytes = self.write_int3(addr)?;
+            ptrace::cont(self.pid, None)?;
+
+            loop {
+                let signal = match snapshot_stop(&self.recv, deadline)? {
+                    WaitStatus::Stopped(_, Signal::SIGTRAP) => None,
+                    WaitStatus::Stopped(_, signal) => Some(signal),
+                    _ => None,
+                };
+
+                let mut regs = ptrace::getregs(self.pid)?;
+                if signal.is_none() && regs.rip as usize == addr + 1 {
+                    self.remove_int3(addr, saved_bytes)?;
+                    regs.rip -= 1;
+                    ptrace::setregs(self.pid, regs)?;
+                    break;
+                }
+                ptrace::cont(self.pid, signal)?;
+            }
+        }
+
+        // Only trace forks once setup is done, so the children are ours
+        ptrace::setoptions(
+            self.pid,
+            ptrace::Options::PTRACE_O_TRACEFORK | ptrace::Options::PTRACE_O_EXITKILL,
+        )?;
+
+        Ok(())
+    }
+
+    /// Run the process until execution reaches `breakpoint`, or until it
+    /// exec'd if there is none, and leave it stopped there as a snapshot to
+    /// fork children from with `fork_snapshot()`
+    #[cfg(not(target_arch = "x86_64"))]
+    pub fn run_to_snapshot(
+        &self,
+        _breakpoint: Option<usize>,
+        _timeout: Duration,
+    ) -> SolverResult<()> {
+        Err(SolverError::new(
+            Runner::ArgError,
+            "Snapshots only supported on x86_64",
+        ))
+    }
+
+    /// Fork the snapshot by having it run a clone syscall in place of the
+    /// instruction it is stopped at, then write `mem` to the child. The child
+    /// stays stopped until it is detached.
+    ///
+    /// NOTE: Like all ptrace requests, this has to be called from the thread
+    /// that spawned the process
+    #[cfg(target_arch = "x86_64")]
+    pub fn fork_snapshot(
+        &self,
+        mem: &[MemInput],
+        timeout: Duration,
+    ) -> SolverResult<SnapshotChild> {
+        debug!("Executing fork_snapshot:");
+        let deadline = Instant::now() + timeout;
+        let regs = ptrace::getregs(self.pid)?;
+        let ip = regs.rip as ptrace::AddressType;
+        let saved_bytes = ptrace::read(self.pid, ip)? as usize;
+
+        // 0f 05 is the x86_64 syscall instruction
+        let syscall_bytes = saved_bytes & (std::usize::MAX ^ 0xffff) | 0x050f;
+        ptrace::write(self.pid, ip, syscall_bytes as ptrace::AddressType)?;
+        let mut clone_regs = regs;
+        clone_regs.rax = SYS_CLONE;
+        clone_regs.rdi = SNAPSHOT_CLONE_FLAGS;
+        clone_regs.rsi = 0;
+        clone_regs.rdx = 0;
+        clone_regs.r10 = 0;
+        clone_regs.r8 = 0;
+        ptrace::setregs(self.pid, clone_regs)?;
+
+        // The snapshot stops at the fork event, then after the syscall
+        let mut pid = None;
+        ptrace::step(self.pid, None)?;
+        loop {
+            match snapshot_stop(&self.recv, deadline)? {
+                WaitStatus::PtraceEvent(_, _, libc::PTRACE_EVENT_FORK) => {
+                    pid = Some(Pid::from_raw(ptrace::getevent(self.pid)? as i32));
+                }
+                WaitStatus::Stopped(_, Signal::SIGTRAP) => break,
+                // Other signals are dropped, the snapshot never runs on
+                _ => {}
+            }
+            ptrace::step(self.pid, None)?;
+        }
+
+        // Put the snapshot back the way it was
+        ptrace::write(self.pid, ip, saved_bytes as ptrace::AddressType)?;
+        ptrace::setregs(self.pid, regs)?;
+
+        let pid =
+            pid.ok_or_else(|| SolverError::new(Runner::RunnerError, "snapshot could not fork"))?;
+        let recv = self
+            .inner
+            .lock()
+            .unwrap()
+            .proc_chans
+            .entry(pid)
+            .or_insert_with(ChanPair::new)
+            .take_recv();
+        let child = SnapshotChild {
+            pid,
+            inner: self.inner.clone(),
+            recv,
+            exited: false,
+        };
+
+        // Forked tracees start stopped by SIGSTOP
+        match snapshot_stop(&child.recv, deadline)? {
+            WaitStatus::Stopped(_, Signal::SIGSTOP) => {}
+            status => {
+                return Err(SolverError::new(
+                    Runner::RunnerError,
+                    &format!("Unexpected snapshot child stop {:?}", status),
+                ))
+            }
+        }
+
+        // The child copied the syscall and the clone registers
+        ptrace::write(pid, ip, saved_bytes as ptrace::AddressType)?;
+        ptrace::setregs(pid, regs)?;
+        for mem in mem {
+            self.write_mem_input_to(pid, mem)?;
+        }
+
+        Ok(child)
+    }
+
+    /// Fork the snapshot by having it run a clone syscall in place of the
+    /// instruction it is stopped at, then write `mem` to the child. The child
+    /// stays stopped until it is detached.
+    #[cfg(not(target_arch = "x86_64"))]
+    pub fn fork_snapshot(
+        &self,
+        _mem: &[MemInput],
+        _timeout: Duration,
+    ) -> SolverResult<SnapshotChild> {
+        Err(SolverError::new(
+            Runner::ArgError,
+            "Snapshots only supported on x86_64",
+        ))
+    }
+}
+
+/// A child forked from a snapshot by `ProcessHandle::fork_snapshot()`. It is
+/// killed when dropped before it exited.
+pub struct SnapshotChild {
+    pid: Pid,
+    inner: Arc<Mutex<ProcessWaiterInner>>,
+    recv: Receiver<WaitData>,
+    exited: bool,
+}
+
+impl SnapshotChild {
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    /// wait until the child exits, killing it if it takes longer than
+    /// `timeout`. Returns the exit status and the time waited.
+    pub fn wait(&mut self, timeout: Duration) -> SolverResult<(i32, Duration)> {
+        debug!("Executing SnapshotChild::wait:");
+        let start = Instant::now();
+        loop {
+            let time_left = timeout
+                .checked_sub(start.elapsed())
+                .unwrap_or_else(|| Duration::from_secs(0));
+            let data = self
+                .recv
+                .recv_timeout(time_left)
+                .map_err(|_| SolverError::new(Runner::Timeout, "child timeout"))?;
+
+            let exit_status = match data.status {
+                WaitStatus::Exited(_, code) => code,
+                WaitStatus::Signaled(_, signal, _) => 128 + signal as i32,
+                _ => continue,
+            };
+            self.exited = true;
+            return Ok((exit_status, start.elapsed()));
+        }
+    }
+}
+
+impl Drop for SnapshotChild {
+    fn drop(&mut self) {
+        if !self.exited {
+            let _ = signal::kill(self.pid, Signal::SIGKILL);
+        }
+        self.inner.lock().unwrap().proc_chans.remove(&self.pid);
+    }
 }
 
 /// Mode to run the process under ptrace
@@ -652,6 +1724,17 @@ pub enum PtraceMode {
     Drop,
 }
 
+/// How the process is resumed when it stops under ptrace
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResumeMode {
+    /// Continue until the next signal or breakpoint
+    Continue,
+    /// Single step every instruction inside the counted address range
+    SingleStep,
+    /// Stop at every syscall entry and exit
+    Syscall,
+}
+
 impl PtraceMode {
     /// Is ptrace enabled?
     pub fn enabled(self) -> bool {
@@ -662,6 +1745,13 @@ impl PtraceMode {
     }
 }
 
+// Processes that are never finished still clean up their input files
+impl Drop for Process {
+    fn drop(&mut self) {
+        self.remove_files();
+    }
+}
+
 // Handle running a process
 impl Process {
     pub fn new(path: &Path) -> SolverResult<Process> {
@@ -670,12 +1760,116 @@ impl Process {
             cmd: Command::new(path),
             stdin_input: Vec::new(),
             mem_input: Vec::new(),
+            reg_input: Vec::new(),
+            count_range: None,
+            coverage: None,
+            watched: Vec::new(),
+            inherited_fds: Vec::new(),
+            discard_output: false,
+            env_vars: Vec::new(),
+            fixed_env: None,
+            files: None,
+            socket: None,
+            conversation: None,
+            conversation_thread: None,
             child: None,
             breakpoints: HashMap::new(),
             ptrace_mode: PtraceMode::Never,
+            resume_mode: ResumeMode::Continue,
         })
     }
 
+    /// set the argv, stdin, memory, register, environment variable and file
+    /// input of the process from an `Input`
+    pub fn input(&mut self, input: &Input) -> SolverResult<()> {
+        debug!("Executing input:");
+        self.input_args(input)?;
+        self.input_stdin(input);
+        if let Some(mem) = &input.mem {
+            self.mem_input(mem.clone());
+        }
+        if let Some(reg) = &input.reg {
+            self.reg_input(reg.clone());
+        }
+        if let Some(env) = &input.env {
+            for (name, value) in env {
+                self.env(OsStr::from_bytes(name), OsStr::from_bytes(value));
+            }
+        }
+        Ok(())
+    }
+
+    /// add the argv of an `Input` to the arguments. Each `@@` is replaced
+    /// with the path of a file in a private directory holding the next of the
+    /// input files, which is written when the process starts.
+    pub fn input_args(&mut self, input: &Input) -> SolverResult<()> {
+        debug!("Executing input_args:");
+        let argv = match &input.argv {
+            Some(argv) => argv,
+            None => return Ok(()),
+        };
+
+        for arg in argv {
+            if arg.as_slice() != FILE_PLACEHOLDER {
+                self.arg(OsStr::from_bytes(arg.as_slice()));
+                continue;
+            }
+
+            if self.files.is_none() {
+                self.files = Some(InputFiles::acquire()?);
+            }
+            let files = self.files.as_mut().unwrap();
+            let contents = input
+                .files
+                .as_ref()
+                .and_then(|inputs| inputs.get(files.files.len()))
+                .cloned()
+                .unwrap_or_default();
+            let path = files.add(contents);
+            self.arg(&path);
+        }
+        Ok(())
+    }
+
+    /// set the stdin of an `Input` as the stdin of the process, or as the
+    /// input sent to its socket or in its conversation if it has one
+    pub fn input_stdin(&mut self, input: &Input) {
+        debug!("Executing input_stdin:");
+        let stdin = input.stdin.clone();
+        match (&input.socket, &input.conversation) {
+            (Some(target), _) => self.socket_input(target.clone(), stdin.unwrap_or_default()),
+            (None, Some(conversation)) => {
+                self.conversation_input(conversation.clone(), stdin.unwrap_or_default())
+            }
+            (None, None) => {
+                if let Some(stdin) = stdin {
+                    self.stdin_input(stdin);
+                }
+            }
+        }
+    }
+
+    /// hold `conversation` with the process, sending `input` at its input
+    /// step. stdin stays open until the conversation is over.
+    pub fn conversation_input(&mut self, conversation: Conversation, input: Vec<u8>) {
+        debug!("Executing conversation_input:");
+        self.conversation = Some((conversation, input))
+    }
+
+    /// send `bytes` to the process once it listens on `target`
+    ///
+    /// NOTE: Every process listens on the same address, so processes with
+    /// socket input run one at a time
+    pub fn socket_input(&mut self, target: SocketTarget, bytes: Vec<u8>) {
+        debug!("Executing socket_input:");
+        self.socket = Some((target, bytes))
+    }
+
+    /// remove the input files
+    fn remove_files(&mut self) {
+        self.files = None;
+    }
+
     /// set what stdin should be sent to process
     pub fn stdin_input(&mut self, stdin: Vec<u8>) {
         debug!("Executing stdin_input:");
@@ -688,6 +1882,38 @@ impl Process {
         self.mem_input = mem
     }
 
+    /// set what registers should be set at breakpoints
+    ///
+    /// NOTE: This needs the process to stay under ptrace
+    pub fn reg_input(&mut self, reg: Vec<RegInput>) {
+        debug!("Executing reg_input:");
+        self.reg_input = reg
+    }
+
+    /// only count between the addresses of `range`
+    ///
+    /// NOTE: This needs the process to stay under ptrace
+    pub fn count_range(&mut self, range: CountRange) {
+        debug!("Executing count_range:");
+        self.count_range = Some(range)
+    }
+
+    /// measure `mode` coverage of the basic blocks at `blocks`
+    ///
+    /// NOTE: This needs the process to stay under ptrace
+    pub fn coverage(&mut self, mode: Coverage, blocks: Arc<Vec<usize>>) {
+        debug!("Executing coverage:");
+        self.coverage = Some(CoverageConfig { mode, blocks })
+    }
+
+    /// count how many times execution reaches each address in `addrs`
+    ///
+    /// NOTE: This needs the process to stay under ptrace
+    pub fn watch(&mut self, addrs: Vec<usize>) {
+        debug!("Executing watch:");
+        self.watched = addrs
+    }
+
     /// returns PID of child process
     pub fn child_id(&self) -> Result<u32, SolverError> {
         debug!("Executing child_id:");
@@ -697,6 +1923,33 @@ impl Process {
         }
     }
 
+    /// start the conversation, or write the stdin input and close stdin
+    pub fn send_input(&mut self) -> Result<(), SolverError> {
+        debug!("Executing send_input:");
+        let (conversation, input) = match self.conversation.clone() {
+            Some(conversation) => conversation,
+            None => {
+                self.write_input()?;
+                return self.close_stdin();
+            }
+        };
+
+        let child = self
+            .child
+            .as_mut()
+            .ok_or_else(|| SolverError::new(Runner::RunnerError, "child process not running"))?;
+        match (child.stdin.take(), child.stdout.take()) {
+            (Some(stdin), Some(stdout)) => {
+                self.conversation_thread = Some(conversation.start(input, stdin, stdout));
+                Ok(())
+            }
+            _ => Err(SolverError::new(
+                Runner::IoError,
+                "could not open stdin and stdout",
+            )),
+        }
+    }
+
     /// writes self.stdin_input to the process's stdin
     pub fn write_input(&mut self) -> Result<(), SolverError> {
         debug!("Executing write_input:");
@@ -715,6 +1968,38 @@ impl Process {
         self.cmd.arg(arg);
     }
 
+    /// set an environment variable of the process
+    pub fn env<K: AsRef<OsStr>, V: AsRef<OsStr>>(&mut self, key: K, val: V) {
+        self.env_vars
+            .push((key.as_ref().to_os_string(), val.as_ref().to_os_string()));
+    }
+
+    /// run the process in a fixed environment. Variables set with `env()`
+    /// are added to it.
+    ///
+    /// NOTE: A relative path to the binary is resolved in the fixed working
+    /// directory, if there is one
+    pub fn fixed_env(&mut self, env: FixedEnv) {
+        debug!("Executing fixed_env:");
+        self.fixed_env = Some(env)
+    }
+
+    /// keep `fd` open in the process even if it is close-on-exec
+    ///
+    /// NOTE: `fd` has to stay open until the process is spawned
+    pub fn inherit_fd(&mut self, fd: RawFd) {
+        debug!("Executing inherit_fd:");
+        self.inherited_fds.push(fd)
+    }
+
+    /// send stdout and stderr of the process to /dev/null instead of pipes
+    ///
+    /// NOTE: For processes whose output nobody reads, like snapshots
+    pub fn discard_output(&mut self) {
+        debug!("Executing discard_output:");
+        self.discard_output = true
+    }
+
     /// initialize process according to settings
     pub fn start(&mut self) -> Result<(), SolverError> {
         debug!("Executing start:");
@@ -722,8 +2007,36 @@ impl Process {
             return Err(SolverError::new(Runner::Unknown, "child already running"));
         }
         self.cmd.stdin(Stdio::piped());
-        self.cmd.stdout(Stdio::piped());
-        self.cmd.stderr(Stdio::piped());
+        if self.discard_output {
+            self.cmd.stdout(Stdio::null());
+            self.cmd.stderr(Stdio::null());
+        } else {
+            self.cmd.stdout(Stdio::piped());
+            self.cmd.stderr(Stdio::piped());
+        }
+
+        if let Some(files) = &self.files {
+            files.write()?;
+        }
+
+        if let Some(env) = &self.fixed_env {
+            env.apply(&mut self.cmd);
+        }
+        self.cmd.envs(self.env_vars.iter().cloned());
+
+        if !self.inherited_fds.is_empty() {
+            let fds = self.inherited_fds.clone();
+            // fds are close-on-exec so other children can't keep them open
+            unsafe {
+                self.cmd.pre_exec(move || {
+                    for fd in &fds {
+                        fcntl(*fd, FcntlArg::F_SETFD(FdFlag::empty()))
+                            .map_err(|_| Error::last_os_error())?;
+                    }
+                    Ok(())
+                });
+            }
+        }
 
         if self.ptrace_mode.enabled() {
             // Copied from spawn_ptrace
@@ -735,11 +2048,17 @@ impl Process {
             }
         }
 
+        // Wait for the previous process with socket input to exit
+        let lease = self.socket.as_ref().map(|_| SocketLease::acquire());
         let child = self.cmd.spawn();
 
         // spawn process and wait after fork
         match child {
             Ok(c) => {
+                if let (Some((target, bytes)), Some(lease)) = (self.socket.clone(), lease) {
+                    let pid = Pid::from_raw(c.id() as i32);
+                    socket::send_input(target, bytes, pid, lease);
+                }
                 self.child = Some(c);
                 Ok(())
             }
@@ -789,6 +2108,12 @@ impl Process {
         self.ptrace_mode = mode;
     }
 
+    /// set how the process is resumed when it stops under ptrace
+    pub fn with_resume_mode(&mut self, mode: ResumeMode) {
+        debug!("Executing with_resume_mode:");
+        self.resume_mode = mode;
+    }
+
     /// spawn process
     pub fn spawn(self) -> ProcessHandle {
         debug!("Executing spawn:");
@@ -0,0 +1,233 @@
+use crate::errors::*;
+use crate::generators::{Input, MemInput};
+use crate::process::{FixedEnv, Process, ProcessHandle, PtraceMode, SnapshotChild};
+use nix::sys::ptrace;
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// What happened to a child forked from a snapshot
+#[derive(Debug, Clone)]
+pub struct SnapshotReport {
+    /// Exit code, or 128 plus the signal that killed the child
+    pub exit_status: i32,
+    /// Time from starting the child until it exited
+    pub wall_time: Duration,
+}
+
+/// Requests to the thread that traces a snapshot. ptrace only accepts
+/// requests from the thread that attached, so the snapshot and its children
+/// are only touched there.
+enum Request {
+    /// Fork a child with memory input written to it
+    Fork(Vec<MemInput>, Duration, Sender<SolverResult<SnapshotChild>>),
+    /// Let a forked child run
+    Start(Pid, Sender<SolverResult<()>>),
+}
+
+/// A target stopped at a memory input breakpoint, after running everything
+/// before it once. Every input runs in a child forked from there.
+#[derive(Debug)]
+pub struct Snapshot {
+    path: PathBuf,
+    argv: Option<Vec<Vec<u8>>>,
+    stdin: Option<Vec<u8>>,
+    env: Option<Vec<(Vec<u8>, Vec<u8>)>>,
+    files: Option<Vec<Vec<u8>>>,
+    breakpoint: Option<usize>,
+    requests: Mutex<Sender<Request>>,
+}
+
+impl Snapshot {
+    /// Run the target at `path` with the argv, stdin and environment of `input` until it
+    /// reaches `breakpoint`, in a thread that keeps it stopped there. The target is
+    /// started in the fixed environment `env` if there is one.
+    pub fn spawn(
+        path: &Path,
+        env: Option<&FixedEnv>,
+        input: &Input,
+        breakpoint: Option<usize>,
+        timeout: Duration,
+    ) -> SolverResult<Snapshot> {
+        debug!("Executing Snapshot::spawn:");
+        let input = Input {
+            mem: None,
+            ..input.clone()
+        };
+        let mut process = Process::new(path)?;
+        if let Some(env) = env {
+            process.fixed_env(env.clone());
+        }
+        process.input(&input)?;
+        process.with_ptrace_mode(PtraceMode::Always);
+        // Nobody reads the output the children share
+        process.discard_output();
+
+        let (requests, recv) = channel();
+        let (ready_send, ready) = channel();
+        thread::spawn(move || {
+            let handle = process.spawn();
+            match handle.run_to_snapshot(breakpoint, timeout) {
+                Ok(()) => {
+                    let _ = ready_send.send(Ok(()));
+                    serve(&handle, recv);
+                }
+                Err(e) => {
+                    let _ = ready_send.send(Err(e));
+                }
+            }
+            let _ = kill(handle.pid(), Signal::SIGKILL);
+        });
+        ready.recv().unwrap_or_else(|_| Err(snapshot_stopped()))?;
+
+        Ok(Snapshot {
+            path: path.to_path_buf(),
+            argv: input.argv,
+            stdin: input.stdin,
+            env: input.env,
+            files: input.files,
+            breakpoint,
+            requests: Mutex::new(requests),
+        })
+    }
+
+    /// Whether the snapshot was taken of a run that `input` would also make
+    fn matches(&self, path: &Path, input: &Input, breakpoint: Option<usize>) -> bool {
+        self.path == path
+            && self.argv == input.argv
+            && self.stdin == input.stdin
+            && self.env == input.env
+            && self.files == input.files
+            && self.breakpoint == breakpoint
+    }
+
+    /// Send a request to the snapshot thread and wait for the reply
+    fn request<T, F>(&self, request: F) -> SolverResult<T>
+    where
+        F: FnOnce(Sender<SolverResult<T>>) -> Request,
+    {
+        let (reply, recv) = channel();
+        self.requests
+            .lock()
+            .unwrap()
+            .send(request(reply))
+            .map_err(|_| snapshot_stopped())?;
+        recv.recv().unwrap_or_else(|_| Err(snapshot_stopped()))
+    }
+
+    /// run a child with `mem` written to it. `attach` is called with the
+    /// child's pid before it starts, to set up counters.
+    pub fn run<T, F>(
+        &self,
+        mem: &[MemInput],
+        timeout: Duration,
+        attach: F,
+    ) -> SolverResult<(T, SnapshotReport)>
+    where
+        F: FnOnce(Pid) -> SolverResult<T>,
+    {
+        debug!("Executing Snapshot::run:");
+        let mut child = self.request(|reply| Request::Fork(mem.to_vec(), timeout, reply))?;
+        let attached = attach(child.pid())?;
+        self.request(|reply| Request::Start(child.pid(), reply))?;
+        let (exit_status, wall_time) = child.wait(timeout)?;
+
+        Ok((
+            attached,
+            SnapshotReport {
+                exit_status,
+                wall_time,
+            },
+        ))
+    }
+}
+
+/// Serve requests until the `Snapshot` is dropped
+fn serve(handle: &ProcessHandle, requests: Receiver<Request>) {
+    for request in requests {
+        match request {
+            Request::Fork(mem, timeout, reply) => {
+                let _ = reply.send(handle.fork_snapshot(&mem, timeout));
+            }
+            Request::Start(pid, reply) => {
+                let _ = reply.send(ptrace::detach(pid).map_err(Into::into));
+            }
+        }
+    }
+}
+
+fn snapshot_stopped() -> SolverError {
+    SolverError::new(Runner::RunnerError, "snapshot thread stopped")
+}
+
+/// The snapshot inputs are currently forked from. A new one is taken when an
+/// input needs a different run up to the breakpoint.
+#[derive(Debug, Default)]
+pub struct SnapshotCache {
+    current: Mutex<Option<Arc<Snapshot>>>,
+}
+
+impl SnapshotCache {
+    pub fn new() -> SnapshotCache {
+        SnapshotCache::default()
+    }
+
+    /// run the memory input of `input` in a child of a snapshot of the
+    /// binary at `path`, started in the fixed environment `env` if there is
+    /// one. All memory inputs have to share one breakpoint, the snapshot is
+    /// taken there.
+    pub fn run<T, F>(
+        &self,
+        path: &Path,
+        env: Option<&FixedEnv>,
+        input: &Input,
+        timeout: Duration,
+        attach: F,
+    ) -> SolverResult<(T, SnapshotReport)>
+    where
+        F: FnOnce(Pid) -> SolverResult<T>,
+    {
+        debug!("Executing SnapshotCache::run:");
+        if input.socket.is_some() || input.conversation.is_some() {
+            return Err(SolverError::new(
+                Runner::ArgError,
+                "Socket and conversation input need the target spawned for each input",
+            ));
+        }
+        if input.reg.is_some() {
+            return Err(SolverError::new(
+                Runner::ArgError,
+                "Register input can't be combined with memory snapshots",
+            ));
+        }
+        let mem = input.mem.clone().unwrap_or_default();
+        let breakpoint = mem.first().and_then(|mem| mem.breakpoint);
+        if mem.iter().any(|mem| mem.breakpoint != breakpoint) {
+            return Err(SolverError::new(
+                Runner::ArgError,
+                "Memory inputs of a snapshot must share one breakpoint",
+            ));
+        }
+
+        let snapshot = {
+            let mut current = self.current.lock().unwrap();
+            match &*current {
+                Some(snapshot) if snapshot.matches(path, input, breakpoint) => snapshot.clone(),
+                _ => {
+                    // Stop the old snapshot before starting the new one
+                    *current = None;
+                    let snapshot =
+                        Arc::new(Snapshot::spawn(path, env, input, breakpoint, timeout)?);
+                    *current = Some(snapshot.clone());
+                    snapshot
+                }
+            }
+        };
+
+        snapshot.run(&mem, timeout, attach)
+    }
+}
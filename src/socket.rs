@@ -0,0 +1,204 @@
+use crate::errors::*;
+use lazy_static::lazy_static;
+use nix::sys::signal::kill;
+use nix::unistd::Pid;
+use std::fs;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{Shutdown, TcpStream, UdpSocket};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long to wait for the target to listen and to close the connection
+const SOCKET_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Time between checks whether the target listens
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+lazy_static! {
+    /// Whether a target with socket input is running. Every target listens
+    /// on the same address, so only one can run at a time.
+    static ref SOCKET_BUSY: (Mutex<bool>, Condvar) = (Mutex::new(false), Condvar::new());
+}
+
+/// Where a network service target listens for its input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SocketTarget {
+    /// TCP port on localhost
+    Tcp(u16),
+    /// UDP port on localhost
+    Udp(u16),
+    /// Unix stream socket
+    Unix(PathBuf),
+}
+
+impl SocketTarget {
+    /// Parse a socket target from an argument of the format:
+    ///
+    /// ``` text
+    /// tcp:PORT | udp:PORT | unix:PATH
+    /// ```
+    pub fn parse_from_arg(arg: &str) -> SolverResult<Self> {
+        debug!("Executing parse_from_arg:");
+        let mut parts = arg.splitn(2, ':');
+        let port = |port: &str| {
+            port.parse()
+                .map_err(|_| SolverError::new(Runner::ArgError, "Invalid socket port"))
+        };
+        match (parts.next(), parts.next()) {
+            (Some("tcp"), Some(p)) => Ok(SocketTarget::Tcp(port(p)?)),
+            (Some("udp"), Some(p)) => Ok(SocketTarget::Udp(port(p)?)),
+            (Some("unix"), Some(path)) if !path.is_empty() => {
+                Ok(SocketTarget::Unix(PathBuf::from(path)))
+            }
+            _ => Err(SolverError::new(
+                Runner::ArgError,
+                "Socket must be tcp:PORT, udp:PORT or unix:PATH",
+            )),
+        }
+    }
+
+    /// Whether a UDP socket is bound to `port`, according to /proc/net
+    fn udp_bound(port: u16) -> bool {
+        ["/proc/net/udp", "/proc/net/udp6"].iter().any(|table| {
+            fs::read_to_string(table)
+                .unwrap_or_default()
+                .lines()
+                .skip(1)
+                .filter_map(|line| line.split_whitespace().nth(1))
+                .filter_map(|local| local.rsplit(':').next())
+                .any(|local_port| u16::from_str_radix(local_port, 0x10) == Ok(port))
+        })
+    }
+
+    /// Try once to deliver `bytes`, returning whether the target listened.
+    /// Streams are drained until the target closes them, so it never writes
+    /// to a closed connection.
+    fn try_send(&self, bytes: &[u8]) -> SolverResult<bool> {
+        let mut stream: Box<dyn ReadWrite> = match self {
+            SocketTarget::Tcp(port) => match TcpStream::connect(("127.0.0.1", *port)) {
+                Ok(stream) => {
+                    stream.set_read_timeout(Some(POLL_INTERVAL))?;
+                    Box::new(stream)
+                }
+                Err(_) => return Ok(false),
+            },
+            SocketTarget::Unix(path) => match UnixStream::connect(path) {
+                Ok(stream) => {
+                    stream.set_read_timeout(Some(POLL_INTERVAL))?;
+                    Box::new(stream)
+                }
+                Err(_) => return Ok(false),
+            },
+            SocketTarget::Udp(port) => {
+                if !SocketTarget::udp_bound(*port) {
+                    return Ok(false);
+                }
+                let socket = UdpSocket::bind(("127.0.0.1", 0))?;
+                socket.send_to(bytes, ("127.0.0.1", *port))?;
+                return Ok(true);
+            }
+        };
+
+        stream.write_all(bytes)?;
+        stream.shutdown_write();
+        let deadline = Instant::now() + SOCKET_TIMEOUT;
+        let mut buf = [0; 0x1000];
+        while Instant::now() < deadline {
+            match stream.read(&mut buf) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(ref e)
+                    if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {}
+                Err(_) => break,
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Streams that can be drained and half closed
+trait ReadWrite: Read + Write {
+    fn shutdown_write(&self);
+}
+
+impl ReadWrite for TcpStream {
+    fn shutdown_write(&self) {
+        let _ = self.shutdown(Shutdown::Write);
+    }
+}
+
+impl ReadWrite for UnixStream {
+    fn shutdown_write(&self) {
+        let _ = self.shutdown(Shutdown::Write);
+    }
+}
+
+/// Permission to run a target with socket input, held until it is dropped
+#[derive(Debug)]
+pub(crate) struct SocketLease;
+
+impl SocketLease {
+    /// Wait until no other target with socket input runs
+    pub(crate) fn acquire() -> SocketLease {
+        let (busy, cvar) = &*SOCKET_BUSY;
+        let mut busy = busy.lock().unwrap();
+        while *busy {
+            busy = cvar.wait(busy).unwrap();
+        }
+        *busy = true;
+        SocketLease
+    }
+}
+
+impl Drop for SocketLease {
+    fn drop(&mut self) {
+        let (busy, cvar) = &*SOCKET_BUSY;
+        *busy.lock().unwrap() = false;
+        cvar.notify_one();
+    }
+}
+
+/// Send `bytes` to the target `pid` once it listens on `target`, from a
+/// thread that keeps `lease` until the target exited
+pub(crate) fn send_input(target: SocketTarget, bytes: Vec<u8>, pid: Pid, lease: SocketLease) {
+    debug!("Executing send_input:");
+    thread::spawn(move || {
+        let deadline = Instant::now() + SOCKET_TIMEOUT;
+        let mut sent = false;
+        // The lease is kept until the target exited, since it may still
+        // listen after it got the input
+        while Instant::now() < deadline && kill(pid, None).is_ok() {
+            if !sent {
+                sent = target.try_send(&bytes).unwrap_or_else(|e| {
+                    warn!("Failed to send socket input: {:?}", e);
+                    true
+                });
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        drop(lease);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SocketTarget;
+    use std::path::PathBuf;
+
+    #[test]
+    fn parse_targets() {
+        assert_eq!(
+            SocketTarget::parse_from_arg("tcp:4444").unwrap(),
+            SocketTarget::Tcp(4444)
+        );
+        assert_eq!(
+            SocketTarget::parse_from_arg("unix:/tmp/b7.sock").unwrap(),
+            SocketTarget::Unix(PathBuf::from("/tmp/b7.sock"))
+        );
+        assert!(SocketTarget::parse_from_arg("udp:70000").is_err());
+        assert!(SocketTarget::parse_from_arg("sctp:4444").is_err());
+    }
+}
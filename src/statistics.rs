@@ -1,5 +1,7 @@
+use crate::errors::*;
 use crate::generators::Input;
 use std::fmt::Debug;
+use std::sync::Mutex;
 extern crate env_logger;
 extern crate log;
 
@@ -15,6 +17,17 @@ fn get_average(input: &[i64]) -> i64 {
     }) / (input.len() as i64)
 }
 
+/// Median absolute deviations the outlier has to be away from the median of
+/// the other counts to stand out
+const OUTLIER_MADS: i64 = 5;
+
+/// returns median of slice
+fn get_median(input: &[i64]) -> i64 {
+    let mut sorted = input.to_vec();
+    sorted.sort();
+    sorted[sorted.len() / 2]
+}
+
 /// find the largest outlier in given slice
 pub fn find_outlier<I: Debug>(counts: &[(i64, (I, Input))]) -> &(i64, (I, Input)) {
     debug! {"Executing find_outlier:"};
@@ -33,9 +46,455 @@ pub fn find_outlier<I: Debug>(counts: &[(i64, (I, Input))]) -> &(i64, (I, Input)
     &counts[max_idx]
 }
 
+/// whether the count at `idx` stands out from the other counts in
+/// `direction`, instead of being the furthest point of noise. It has to be
+/// more than `OUTLIER_MADS` median absolute deviations away from their
+/// median, or differ from them at all if they are equal.
+pub fn stands_out_toward(counts: &[i64], idx: usize, direction: Direction) -> bool {
+    debug!("Executing stands_out_toward:");
+    let outlier = counts[idx];
+    let others = without(counts, idx);
+    if others.is_empty() {
+        return true;
+    }
+
+    let median = get_median(&others);
+    let deviations: Vec<i64> = others.iter().map(|count| (count - median).abs()).collect();
+    let mad = get_median(&deviations);
+    distance(outlier, median as f64, direction) > (OUTLIER_MADS * mad) as f64
+}
+
+/// rank the counts by how far they are from the median in `direction`, in
+/// median absolute deviations, returning their indices with the distance,
+/// furthest first. Counts on the other side of the median get a negative
+/// distance.
+pub fn rank_outliers<I: Debug>(
+    counts: &[(i64, (I, Input))],
+    direction: Direction,
+) -> Vec<(usize, f64)> {
+    debug!("Executing rank_outliers:");
+    let values: Vec<i64> = counts.iter().map(|i| i.0).collect();
+    let median = get_median(&values);
+    let deviations: Vec<i64> = values.iter().map(|count| (count - median).abs()).collect();
+    let mad = std::cmp::max(get_median(&deviations), 1) as f64;
+
+    let mut ranked: Vec<(usize, f64)> = values
+        .iter()
+        .enumerate()
+        .map(|(idx, &count)| (idx, distance(count, median as f64, direction) / mad))
+        .collect();
+    ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    ranked
+}
+
+/// The count an `OutlierDetector` picked as the outlier of a round
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pick {
+    /// Index of the count
+    pub idx: usize,
+    /// How sure the detector is that the count stands out instead of being
+    /// noise, from 0 to 1
+    pub confidence: f64,
+}
+
+/// Which way the outlier of a round differs from the other counts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// The target does more work for a correct input
+    Higher,
+    /// The target does less work for a correct input, like exiting early
+    Lower,
+    /// Either way
+    Any,
+}
+
+impl Default for Direction {
+    fn default() -> Self {
+        Direction::Any
+    }
+}
+
+impl Direction {
+    /// Parse a direction from one of the names `higher`, `lower` and `any`
+    pub fn parse_from_arg(arg: &str) -> SolverResult<Self> {
+        debug!("Executing parse_from_arg:");
+        match arg {
+            "higher" => Ok(Direction::Higher),
+            "lower" => Ok(Direction::Lower),
+            "any" => Ok(Direction::Any),
+            _ => Err(SolverError::new(
+                Runner::ArgError,
+                &format!("Unknown outlier direction {}", arg),
+            )),
+        }
+    }
+}
+
+/// Picks the outlier of a round of counts and says how sure it is
+pub trait OutlierDetector: Send + Sync {
+    /// Pick the outlier of `counts` that differs from the others in
+    /// `direction`, or `None` if there are no counts
+    fn pick_toward(&self, counts: &[i64], direction: Direction) -> Option<Pick>;
+
+    /// Pick the outlier of `counts` in the direction of the detector
+    fn pick(&self, counts: &[i64]) -> Option<Pick> {
+        self.pick_toward(counts, self.direction())
+    }
+
+    /// Direction the detector picks outliers in
+    fn direction(&self) -> Direction {
+        Direction::Any
+    }
+
+    /// Whether the count at `idx` stands out from the other `counts` in the
+    /// direction of the detector
+    fn stands_out(&self, counts: &[i64], idx: usize) -> bool {
+        stands_out_toward(counts, idx, self.direction())
+    }
+
+    /// Learn from `pick`, the outlier of `counts` a search went on with.
+    /// Rounds that are re-run or only tried aren't committed.
+    fn commit(&self, _counts: &[i64], _pick: Pick) {}
+
+    /// Forget what was learned, before a search of another input starts
+    fn reset(&self) {}
+}
+
+/// Picks the count furthest from the median. The confidence is how unlikely
+/// it is that any of the counts is that far from the median by chance, if
+/// the other counts are normally distributed with the spread their median
+/// absolute deviation suggests.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MedianMad;
+
+impl OutlierDetector for MedianMad {
+    fn pick_toward(&self, counts: &[i64], direction: Direction) -> Option<Pick> {
+        debug!("Executing MedianMad::pick_toward:");
+        let median = get_median_f(counts)?;
+        let idx = furthest_from(counts, median, direction)?;
+        let others = without(counts, idx);
+        let confidence = match get_median_f(&others) {
+            Some(median) => {
+                let deviations: Vec<f64> =
+                    others.iter().map(|&c| (c as f64 - median).abs()).collect();
+                let mad = get_median_of(deviations);
+                // Scale the MAD to the standard deviation of normal noise
+                let deviation = distance(counts[idx], median, direction).max(0.0);
+                let z = deviation_score(deviation, MAD_SCALE * mad);
+                normal_confidence(z, counts.len())
+            }
+            None => 1.0,
+        };
+        Some(Pick { idx, confidence })
+    }
+}
+
+/// Picks the count furthest from the mean, like `find_outlier`. The
+/// confidence is the one of `MedianMad`, with the mean and standard
+/// deviation of the other counts instead of their median and MAD.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ZScore;
+
+impl OutlierDetector for ZScore {
+    fn pick_toward(&self, counts: &[i64], direction: Direction) -> Option<Pick> {
+        debug!("Executing ZScore::pick_toward:");
+        let mean = get_mean(counts)?;
+        let idx = furthest_from(counts, mean, direction)?;
+        let others = without(counts, idx);
+        let confidence = match get_mean(&others) {
+            Some(mean) => {
+                let variance = others
+                    .iter()
+                    .map(|&c| (c as f64 - mean).powi(2))
+                    .sum::<f64>()
+                    / others.len() as f64;
+                let deviation = distance(counts[idx], mean, direction).max(0.0);
+                let z = deviation_score(deviation, variance.sqrt());
+                normal_confidence(z, counts.len())
+            }
+            None => 1.0,
+        };
+        Some(Pick { idx, confidence })
+    }
+}
+
+/// Picks the count furthest from the median. The confidence is how much
+/// further it is than the runner-up, relative to its own distance, so a
+/// clear winner gets close to 1 and a tie gets 0.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Gap;
+
+impl OutlierDetector for Gap {
+    fn pick_toward(&self, counts: &[i64], direction: Direction) -> Option<Pick> {
+        debug!("Executing Gap::pick_toward:");
+        let median = get_median_f(counts)?;
+        let idx = furthest_from(counts, median, direction)?;
+        let first = distance(counts[idx], median, direction).max(0.0);
+        let second = without(counts, idx)
+            .iter()
+            .map(|&c| distance(c, median, direction).max(0.0))
+            .fold(None, |max: Option<f64>, dist| {
+                Some(max.map_or(dist, |max| max.max(dist)))
+            });
+        let confidence = match second {
+            Some(_) if first <= 0.0 => 0.0,
+            Some(second) => (first - second) / first,
+            None => 1.0,
+        };
+        Some(Pick { idx, confidence })
+    }
+}
+
+/// Confident rounds `Directed::auto` learns the direction from
+const AUTO_ROUNDS: u32 = 3;
+
+/// Confidence a round needs for `Directed::auto` to learn from it
+const AUTO_CONFIDENCE: f64 = 0.95;
+
+/// Picks outliers with another detector, but only in one direction. The
+/// direction is either fixed, or learned from the first confident rounds a
+/// search commits to and then held until it is reset, so a spurious
+/// outlier the other way can't be picked.
+pub struct Directed {
+    detector: Box<dyn OutlierDetector>,
+    /// Fixed direction, or `None` to learn it
+    direction: Option<Direction>,
+    /// Confident rounds whose outlier was higher and lower, and the
+    /// direction learned from them
+    learned: Mutex<(u32, u32, Option<Direction>)>,
+}
+
+impl Directed {
+    /// Pick outliers with `detector` in `direction` only
+    pub fn new(detector: Box<dyn OutlierDetector>, direction: Direction) -> Directed {
+        Directed {
+            detector,
+            direction: Some(direction),
+            learned: Mutex::new((0, 0, None)),
+        }
+    }
+
+    /// Pick outliers with `detector` in the direction most of the first
+    /// `AUTO_ROUNDS` confident rounds had them
+    pub fn auto(detector: Box<dyn OutlierDetector>) -> Directed {
+        Directed {
+            detector,
+            direction: None,
+            learned: Mutex::new((0, 0, None)),
+        }
+    }
+
+    /// Pick outliers with `detector` in a direction parsed from one of the
+    /// names `higher`, `lower`, `any` and `auto`
+    pub fn parse_from_arg(detector: Box<dyn OutlierDetector>, arg: &str) -> SolverResult<Self> {
+        debug!("Executing parse_from_arg:");
+        match arg {
+            "auto" => Ok(Directed::auto(detector)),
+            _ => Ok(Directed::new(detector, Direction::parse_from_arg(arg)?)),
+        }
+    }
+}
+
+impl OutlierDetector for Directed {
+    fn pick_toward(&self, counts: &[i64], direction: Direction) -> Option<Pick> {
+        self.detector.pick_toward(counts, direction)
+    }
+
+    fn direction(&self) -> Direction {
+        match self.direction {
+            Some(direction) => direction,
+            None => self.learned.lock().unwrap().2.unwrap_or(Direction::Any),
+        }
+    }
+
+    /// Learn which way the confident outliers differ
+    fn commit(&self, counts: &[i64], pick: Pick) {
+        debug!("Executing Directed::commit:");
+        let mut learned = self.learned.lock().unwrap();
+        if self.direction.is_some() || learned.2.is_some() || pick.confidence < AUTO_CONFIDENCE {
+            return;
+        }
+
+        let median = get_median_f(&without(counts, pick.idx)).unwrap_or(0.0);
+        if counts[pick.idx] as f64 > median {
+            learned.0 += 1;
+        } else if (counts[pick.idx] as f64) < median {
+            learned.1 += 1;
+        }
+        if learned.0 + learned.1 >= AUTO_ROUNDS {
+            let direction = if learned.0 > learned.1 {
+                Direction::Higher
+            } else {
+                Direction::Lower
+            };
+            info!("Outliers are {:?}, holding that direction", direction);
+            learned.2 = Some(direction);
+        }
+    }
+
+    fn reset(&self) {
+        *self.learned.lock().unwrap() = (0, 0, None);
+    }
+}
+
+/// Parse an outlier detector from one of the names `mad`, `zscore` and `gap`
+pub fn detector_from_arg(arg: &str) -> SolverResult<Box<dyn OutlierDetector>> {
+    debug!("Executing detector_from_arg:");
+    match arg {
+        "mad" => Ok(Box::new(MedianMad)),
+        "zscore" => Ok(Box::new(ZScore)),
+        "gap" => Ok(Box::new(Gap)),
+        _ => Err(SolverError::new(
+            Runner::ArgError,
+            &format!("Unknown outlier detector {}", arg),
+        )),
+    }
+}
+
+/// Standard deviations of the calibration runs that make up the noise floor
+const FLOOR_DEVIATIONS: f64 = 3.0;
+
+/// Noise of a solver, measured by running one input many times
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoiseFloor {
+    /// `FLOOR_DEVIATIONS` standard deviations of the runs, estimated from
+    /// their median absolute deviation so a few wild runs don't raise it. A
+    /// count has to be further than this from the other counts of its round
+    /// to be a signal.
+    pub floor: i64,
+}
+
+impl NoiseFloor {
+    /// Noise floor of the counts of runs of one input, or `None` with fewer
+    /// than 2 counts
+    pub fn from_counts(counts: &[i64]) -> Option<NoiseFloor> {
+        debug!("Executing NoiseFloor::from_counts:");
+        if counts.len() < 2 {
+            return None;
+        }
+        let median = get_median(counts);
+        let deviations: Vec<i64> = counts.iter().map(|count| (count - median).abs()).collect();
+        let mad = get_median(&deviations);
+        let floor = (FLOOR_DEVIATIONS * MAD_SCALE * mad as f64).ceil() as i64;
+        Some(NoiseFloor { floor })
+    }
+
+    /// Whether the count at `idx` is further than the floor from the median
+    /// of the other counts
+    pub fn is_signal(&self, counts: &[i64], idx: usize) -> bool {
+        match get_median_f(&without(counts, idx)) {
+            Some(median) => (counts[idx] as f64 - median).abs() > self.floor as f64,
+            None => true,
+        }
+    }
+}
+
+/// Scales a median absolute deviation to the standard deviation of normally
+/// distributed values
+const MAD_SCALE: f64 = 1.4826;
+
+/// mean of slice, or `None` if it is empty
+fn get_mean(input: &[i64]) -> Option<f64> {
+    if input.is_empty() {
+        return None;
+    }
+    Some(input.iter().map(|&i| i as f64).sum::<f64>() / input.len() as f64)
+}
+
+/// median of slice, averaging the middle two of an even length, or `None`
+/// if it is empty
+fn get_median_f(input: &[i64]) -> Option<f64> {
+    if input.is_empty() {
+        return None;
+    }
+    Some(get_median_of(input.iter().map(|&i| i as f64).collect()))
+}
+
+fn get_median_of(mut input: Vec<f64>) -> f64 {
+    input.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let mid = input.len() / 2;
+    if input.len() % 2 == 0 {
+        (input[mid - 1] + input[mid]) / 2.0
+    } else {
+        input[mid]
+    }
+}
+
+/// how far `count` is from `center` in `direction`, negative if it is on
+/// the other side
+fn distance(count: i64, center: f64, direction: Direction) -> f64 {
+    match direction {
+        Direction::Higher => count as f64 - center,
+        Direction::Lower => center - count as f64,
+        Direction::Any => (count as f64 - center).abs(),
+    }
+}
+
+/// index of the first count furthest from `center` in `direction`
+fn furthest_from(counts: &[i64], center: f64, direction: Direction) -> Option<usize> {
+    let mut furthest: Option<(usize, f64)> = None;
+    for (idx, &count) in counts.iter().enumerate() {
+        let dist = distance(count, center, direction);
+        if furthest.map_or(true, |(_, max)| dist > max) {
+            furthest = Some((idx, dist));
+        }
+    }
+    furthest.map(|(idx, _)| idx)
+}
+
+/// the counts except the one at `idx`
+fn without(counts: &[i64], idx: usize) -> Vec<i64> {
+    let mut others = counts.to_vec();
+    others.remove(idx);
+    others
+}
+
+/// `deviation` in units of `spread`. Without any spread, any deviation
+/// stands out infinitely.
+fn deviation_score(deviation: f64, spread: f64) -> f64 {
+    if spread > 0.0 {
+        deviation / spread
+    } else if deviation > 0.0 {
+        std::f64::INFINITY
+    } else {
+        0.0
+    }
+}
+
+/// Probability that none of `n` standard normal values is `z` or more away
+/// from 0, so a `z` that noise easily reaches in a large round gets a low
+/// confidence
+fn normal_confidence(z: f64, n: usize) -> f64 {
+    if z.is_infinite() {
+        return 1.0;
+    }
+    if z <= 0.0 {
+        return 0.0;
+    }
+    erf(z / std::f64::consts::SQRT_2).powi(n as i32)
+}
+
+/// Error function, after Abramowitz and Stegun 7.1.26. Accurate to 1.5e-7.
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.327_591_1 * x.abs());
+    let poly = t
+        * (0.254_829_592
+            + t * (-0.284_496_736
+                + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    let erf = 1.0 - poly * (-x * x).exp();
+    if x < 0.0 {
+        -erf
+    } else {
+        erf
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{find_outlier, get_average};
+    use super::{
+        detector_from_arg, find_outlier, get_average, rank_outliers, Directed, Direction, Gap,
+        MedianMad, NoiseFloor, OutlierDetector, ZScore,
+    };
     use crate::generators::Input;
 
     #[test]
@@ -71,4 +530,94 @@ mod tests {
         debug!("Executing outlier_test_panic:");
         find_outlier(&[] as &[(i64, (u32, Input))]);
     }
+
+    #[test]
+    fn rank_test() {
+        debug!("Executing rank_test:");
+        let counts: Vec<(i64, (u32, Input))> = [100, 90, 102, 130, 98]
+            .iter()
+            .map(|&c| (c, (0, Input::new())))
+            .collect();
+        let ranked: Vec<usize> = rank_outliers(&counts, Direction::Any)
+            .iter()
+            .map(|r| r.0)
+            .collect();
+        assert_eq!(ranked, vec![3, 1, 2, 4, 0]);
+        let ranked: Vec<usize> = rank_outliers(&counts, Direction::Lower)
+            .iter()
+            .map(|r| r.0)
+            .collect();
+        assert_eq!(ranked, vec![1, 4, 0, 2, 3]);
+    }
+
+    #[test]
+    fn detector_test() {
+        debug!("Executing detector_test:");
+        let noisy = [100, 102, 98, 101, 150, 99];
+        let flat = [100, 102, 98, 101, 104];
+        let detectors: [&dyn OutlierDetector; 3] = [&MedianMad, &ZScore, &Gap];
+        for detector in detectors.iter() {
+            let pick = detector.pick(&noisy).unwrap();
+            assert_eq!(pick.idx, 4);
+            assert!(pick.confidence > 0.9);
+            assert!(detector.pick(&flat).unwrap().confidence < pick.confidence);
+            assert_eq!(detector.pick(&[100, 100, 100, 101]).unwrap().idx, 3);
+            assert!(detector.pick(&[]).is_none());
+        }
+        assert_eq!(MedianMad.pick(&[100, 100, 100]).unwrap().confidence, 0.0);
+        assert_eq!(MedianMad.pick(&[100, 100, 101]).unwrap().confidence, 1.0);
+        assert!(detector_from_arg("gap").is_ok());
+        assert!(detector_from_arg("mean").is_err());
+    }
+
+    #[test]
+    fn noise_floor_test() {
+        debug!("Executing noise_floor_test:");
+        assert!(NoiseFloor::from_counts(&[100]).is_none());
+        let floor = NoiseFloor::from_counts(&[100, 103, 98, 101, 100]).unwrap();
+        assert_eq!(floor.floor, 5);
+        assert!(floor.is_signal(&[100, 101, 110, 99], 2));
+        assert!(!floor.is_signal(&[100, 101, 104, 99], 2));
+
+        // A single wild run doesn't raise the floor
+        let floor = NoiseFloor::from_counts(&[100, 101, 99, 100, 100, 160, 101]).unwrap();
+        assert_eq!(floor.floor, 5);
+    }
+
+    #[test]
+    fn direction_test() {
+        debug!("Executing direction_test:");
+        let counts = [100, 101, 40, 120, 99, 100];
+        assert_eq!(MedianMad.pick(&counts).unwrap().idx, 2);
+        assert_eq!(
+            MedianMad
+                .pick_toward(&counts, Direction::Higher)
+                .unwrap()
+                .idx,
+            3
+        );
+        assert_eq!(Gap.pick_toward(&counts, Direction::Lower).unwrap().idx, 2);
+
+        // Only outliers in the direction of the detector stand out
+        let higher = [100, 101, 99, 150, 100];
+        assert!(MedianMad.stands_out(&higher, 3));
+        assert!(!Directed::new(Box::new(MedianMad), Direction::Lower).stands_out(&higher, 3));
+
+        // Learns that outliers are higher from committed rounds only, then
+        // ignores the low one until it is reset
+        let auto = Directed::auto(Box::new(MedianMad));
+        for _ in 0..3 {
+            assert_eq!(auto.pick(&higher).unwrap().idx, 3);
+        }
+        assert_eq!(auto.direction(), Direction::Any);
+        for _ in 0..3 {
+            auto.commit(&higher, auto.pick(&higher).unwrap());
+        }
+        assert_eq!(auto.direction(), Direction::Higher);
+        assert_eq!(auto.pick(&counts).unwrap().idx, 3);
+        auto.reset();
+        assert_eq!(auto.direction(), Direction::Any);
+        assert_eq!(auto.pick(&counts).unwrap().idx, 2);
+        assert!(Directed::parse_from_arg(Box::new(Gap), "sideways").is_err());
+    }
 }
@@ -0,0 +1,57 @@
+use crate::brute::*;
+use crate::errors::*;
+use crate::measurement::Measurement;
+use crate::process::{CountRange, Process, PtraceMode, ResumeMode};
+
+/// Counts instructions by single stepping the target with ptrace. This needs
+/// no perf support and the counts are exact, but every instruction costs a
+/// stop, so restricting it to a count range is recommended.
+#[derive(Debug, Clone, Default)]
+pub struct StepSolver {
+    range: Option<CountRange>,
+}
+
+impl StepSolver {
+    /// Count every instruction the target executes
+    pub fn new() -> StepSolver {
+        StepSolver::default()
+    }
+
+    /// Only count while execution is between the addresses of `range`
+    pub fn range(mut self, range: CountRange) -> StepSolver {
+        self.range = Some(range);
+        self
+    }
+}
+
+impl InstCounter for StepSolver {
+    /// runs the process under ptrace, single stepping it inside the count range
+    ///
+    /// # Return
+    /// * number of instructions that were single stepped or error
+    fn get_inst_count(&self, data: &InstCountData) -> Result<Measurement, SolverError> {
+        debug!("Executing get_inst_count:");
+        if data.drop_ptrace {
+            return Err(SolverError::new(
+                Runner::ArgError,
+                "ptrace dropping and single stepping are mutually exclusive",
+            ));
+        }
+
+        let mut process = Process::new(&data.path)?;
+        process.input(&data.inp)?;
+        process.with_ptrace_mode(PtraceMode::Always);
+        process.with_resume_mode(ResumeMode::SingleStep);
+        if let Some(range) = self.range {
+            process.count_range(range);
+        }
+
+        let mut handle = process.spawn();
+        let report = handle.finish(data.timeout)?;
+        trace!("single stepped {} instructions", report.steps);
+
+        Ok(report
+            .measurement(report.steps as i64)
+            .with("steps", report.steps as i64))
+    }
+}
@@ -0,0 +1,58 @@
+use crate::brute::*;
+use crate::errors::*;
+use crate::measurement::Measurement;
+use crate::process::{Process, PtraceMode, ResumeMode};
+
+/// Counts the syscalls the target makes by stopping it at every syscall with
+/// `PTRACE_SYSCALL`. This catches targets that check one byte per `read()` or
+/// bail out early with `exit`.
+#[derive(Debug, Clone, Default)]
+pub struct SyscallSolver {
+    syscall: Option<u64>,
+}
+
+impl SyscallSolver {
+    /// Count every syscall
+    pub fn new() -> SyscallSolver {
+        SyscallSolver::default()
+    }
+
+    /// Only count calls of the syscall numbered `syscall`
+    pub fn syscall(mut self, syscall: u64) -> SyscallSolver {
+        self.syscall = Some(syscall);
+        self
+    }
+}
+
+impl InstCounter for SyscallSolver {
+    /// runs the process under ptrace, stopping at every syscall
+    ///
+    /// # Return
+    /// * number of (selected) syscalls that were made or error
+    fn get_inst_count(&self, data: &InstCountData) -> Result<Measurement, SolverError> {
+        debug!("Executing get_inst_count:");
+        if data.drop_ptrace {
+            return Err(SolverError::new(
+                Runner::ArgError,
+                "ptrace dropping and syscall tracing are mutually exclusive",
+            ));
+        }
+
+        let mut process = Process::new(&data.path)?;
+        process.input(&data.inp)?;
+        process.with_ptrace_mode(PtraceMode::Always);
+        process.with_resume_mode(ResumeMode::Syscall);
+
+        let mut handle = process.spawn();
+        let report = handle.finish(data.timeout)?;
+        trace!("syscalls: {:?}", report.syscalls);
+
+        let total = report.syscalls.values().sum::<u64>() as i64;
+        let count = match self.syscall {
+            Some(nr) => report.syscalls.get(&nr).cloned().unwrap_or(0) as i64,
+            None => total,
+        };
+
+        Ok(report.measurement(count).with("syscalls", total))
+    }
+}